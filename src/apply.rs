@@ -113,6 +113,35 @@ pub fn emit_commandline(
     cli
 }
 
+/// Diff command run by `kubectl diff`, showing the objects in full so that the path of the
+/// changed fields can be told from the diff.
+const FULL_CONTEXT_DIFF: &str = "diff -N -U 1000000";
+
+/// Command line that shows, with a server-side dry-run, what applying the manifests would change.
+///
+/// `kubectl diff` exits with 1 when there are differences, which is not an error here.
+pub fn emit_diff_commandline(app_instance: &AppInstance, manifests_dir: &str) -> Vec<String> {
+    let diff = [
+        &format!("KUBECTL_EXTERNAL_DIFF='{FULL_CONTEXT_DIFF}'"),
+        "kubectl",
+        "diff",
+        "-n",
+        &app_instance.namespace_any(),
+        "--server-side",
+        "--force-conflicts",
+        "--field-manager",
+        KUBIT_APPLIER_FIELD_MANAGER,
+        "-f",
+        manifests_dir,
+    ]
+    .join(" ");
+
+    ["/bin/sh", "-c", &format!("{diff}; test $? -le 1")]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,4 +190,20 @@ mod tests {
 
         assert_eq!(output, expected);
     }
+
+    #[test]
+    fn diff_emit_commandline() {
+        let app_instance = arrange_app_instance();
+
+        let output = emit_diff_commandline(&app_instance, "/manifests");
+
+        assert_eq!(
+            output,
+            vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                format!("KUBECTL_EXTERNAL_DIFF='diff -N -U 1000000' kubectl diff -n test --server-side --force-conflicts --field-manager {KUBIT_APPLIER_FIELD_MANAGER} -f /manifests; test $? -le 1"),
+            ]
+        );
+    }
 }
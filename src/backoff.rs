@@ -0,0 +1,103 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+/// Parameters of the exponential backoff applied to instances that keep failing.
+#[derive(Debug, Clone)]
+pub struct BackoffConfig {
+    /// Delay before the first retry.
+    pub initial: Duration,
+    /// Upper bound of the delay.
+    pub max: Duration,
+    /// Factor the delay is multiplied by after every failure.
+    pub factor: f64,
+    /// Fraction of the delay that is randomly added or removed, to spread the retries.
+    pub jitter: f64,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(5),
+            max: Duration::from_secs(300),
+            factor: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Delay before the retry that follows the given number of consecutive failures.
+    ///
+    /// `random` is a number in `[0, 1)` that determines the jitter.
+    fn delay(&self, failures: u32, random: f64) -> Duration {
+        let exponent = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let base =
+            (self.initial.as_secs_f64() * self.factor.powi(exponent)).min(self.max.as_secs_f64());
+        let jittered = base * (1.0 + self.jitter * (2.0 * random - 1.0));
+        Duration::try_from_secs_f64(jittered.clamp(0.0, self.max.as_secs_f64())).unwrap_or(self.max)
+    }
+}
+
+/// Tracks consecutive failures per instance.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    config: BackoffConfig,
+    failures: Mutex<HashMap<String, u32>>,
+}
+
+impl Backoff {
+    pub fn new(config: BackoffConfig) -> Self {
+        Self {
+            config,
+            failures: Default::default(),
+        }
+    }
+
+    /// Records a failure of the given instance and returns the number of consecutive
+    /// failures so far, together with the delay before the next retry.
+    pub fn failed(&self, key: &str) -> (u32, Duration) {
+        let mut failures = self.failures.lock().expect("backoff lock poisoned");
+        let count = failures.entry(key.to_string()).or_default();
+        *count = count.saturating_add(1);
+        (*count, self.config.delay(*count, fastrand::f64()))
+    }
+
+    /// Forgets the failures of the given instance.
+    pub fn reset(&self, key: &str) {
+        self.failures
+            .lock()
+            .expect("backoff lock poisoned")
+            .remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_delay() {
+        let config = BackoffConfig {
+            initial: Duration::from_secs(5),
+            max: Duration::from_secs(60),
+            factor: 2.0,
+            jitter: 0.2,
+        };
+
+        assert_eq!(config.delay(1, 0.5), Duration::from_secs(5));
+        assert_eq!(config.delay(2, 0.5), Duration::from_secs(10));
+        assert_eq!(config.delay(3, 0.0), Duration::from_secs(16));
+        assert_eq!(config.delay(3, 1.0), Duration::from_secs(24));
+        assert_eq!(config.delay(10, 0.5), Duration::from_secs(60));
+        assert_eq!(config.delay(u32::MAX, 0.5), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn reset() {
+        let backoff = Backoff::new(BackoffConfig::default());
+        assert_eq!(backoff.failed("ns/a").0, 1);
+        assert_eq!(backoff.failed("ns/a").0, 2);
+        assert_eq!(backoff.failed("ns/b").0, 1);
+        backoff.reset("ns/a");
+        assert_eq!(backoff.failed("ns/a").0, 1);
+    }
+}
@@ -1,11 +1,11 @@
-use futures::StreamExt;
+use futures::{stream::FuturesUnordered, Future, FutureExt, StreamExt};
 use itertools::Itertools;
 use k8s_openapi::{
     api::{
         batch::v1::{Job, JobSpec},
         core::v1::{
-            ConfigMap, Container, EnvVar, KeyToPath, Pod, PodSpec, PodTemplateSpec, Secret,
-            SecretVolumeSource, ServiceAccount, Volume, VolumeMount,
+            ConfigMap, Container, EnvVar, KeyToPath, Namespace, ObjectReference, Pod, PodSpec,
+            PodTemplateSpec, Secret, SecretVolumeSource, ServiceAccount, Volume, VolumeMount,
         },
         rbac::v1::{
             ClusterRole, ClusterRoleBinding, PolicyRule, Role, RoleBinding, RoleRef, Subject,
@@ -13,51 +13,118 @@ use k8s_openapi::{
     },
     apimachinery::pkg::apis::meta::v1::{OwnerReference, Time},
     chrono::Utc,
+    ByteString, NamespaceResourceScope,
 };
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
 
 use kube::{
     api::{DeleteParams, ListParams, LogParams, Patch, PatchParams, PostParams, PropagationPolicy},
+    config::{KubeConfigOptions, Kubeconfig},
     core::ObjectMeta,
     error::ErrorResponse,
     runtime::{
         conditions::{is_deleted, is_job_completed, Condition},
         controller::{Action, Controller},
+        events::{Event, EventType, Recorder, Reporter},
         finalizer::{finalizer, Event as Finalizer},
         wait::await_condition,
-        watcher,
+        watcher::{self, watcher},
+        WatchStreamExt,
     },
     Api, Client, Resource, ResourceExt,
 };
 use oci_distribution::{secrets::RegistryAuth, Reference};
+use serde::de::DeserializeOwned;
 
 #[allow(unused_imports)]
 use tracing::{debug, error, info, warn};
 
 use crate::{
     apply::{self},
+    backoff::{Backoff, BackoffConfig},
     delete,
     docker_config::DockerConfig,
-    oci::{self, PackageConfig},
+    drift,
+    engine::{RenderEngine, RenderImages},
+    health::{self, Health},
+    inventory::{self, AppliedObject},
+    logs::{self, LogsConfig},
+    metrics::Metrics,
+    oci::{self, PackageConfig, PermissionRule},
+    redact::Redactor,
     render,
-    resources::{AppInstance, AppInstanceCondition, AppInstanceLikeResources, AppInstanceStatus},
+    resources::{
+        AppInstance, AppInstanceCondition, AppInstanceLikeResources, AppInstanceStatus,
+        JobTemplate, LogsReference,
+    },
     Error, Result,
 };
 
 pub const KUBECTL_IMAGE: &str = "registry.k8s.io/kubectl:v1.28.0";
 
+pub const KUBIT_IMAGE: &str = concat!("ghcr.io/kubecfg/kubit:v", env!("CARGO_PKG_VERSION"));
+
 const APPLIER_SERVICE_ACCOUNT: &str = "kubit-applier";
 
+// Track the cluster-scoped RBAC objects created for the applier.
+const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+const NAMESPACE_LABEL: &str = "kubit.kubecfg.dev/namespace";
+const INSTANCE_ANNOTATION: &str = "kubit.kubecfg.dev/instance";
+
+/// Verbs needed to apply and prune resources.
+const APPLIER_VERBS: [&str; 7] = [
+    "create", "update", "get", "list", "patch", "watch", "delete",
+];
+
 const KUBIT_FINALIZER: &str = "kubecfg.dev/appinstance-cleanup";
 
+const DOCKER_CONFIG_JSON_SECRET_TYPE: &str = "kubernetes.io/dockerconfigjson";
+
+// Annotations recording on the apply job what it is going to install, so that the
+// outcome can be reported in the AppInstance status once the job completes.
+const PACKAGE_DIGEST_ANNOTATION: &str = "kubit.kubecfg.dev/package-digest";
+const KUBECFG_VERSION_ANNOTATION: &str = "kubit.kubecfg.dev/kubecfg-version";
+const GENERATION_ANNOTATION: &str = "kubit.kubecfg.dev/generation";
+
+/// Setting this annotation on an AppInstance to a new value (e.g. the current time)
+/// forces the package to be applied again even if the spec didn't change.
+pub const RECONCILE_REQUESTED_AT_ANNOTATION: &str = "kubit.kubecfg.dev/reconcile-requested-at";
+
+// How often the health of the installed resources is re-assessed once an instance is applied.
+const HEALTHY_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+const UNHEALTHY_RECHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Key of the ConfigMap data holding the AppInstance, unless configured otherwise.
+pub const DEFAULT_CONFIG_MAP_KEY: &str = "app-instance";
+
+// Where the kubeconfig of the target cluster is mounted in the jobs.
+const KUBECONFIG_VOLUME: &str = "kubeconfig";
+const KUBECONFIG_PATH: &str = "/kubeconfig/config";
+
+const APPLY_CONTAINER: &str = "apply-manifests";
+const DRIFT_CHECK_CONTAINER: &str = "diff-manifests";
+
 struct Context {
     client: Client,
-    kubecfg_image: String,
+    render_images: RenderImages,
     kubit_image: String,
     kubectl_image_apply: String,
     kubectl_image_render: String,
-    config_map_name: Option<String>,
+    config_map: Option<ConfigMapSource>,
     only_paused: bool,
+    default_job_template: JobTemplate,
+    backoff: Backoff,
+    metrics: Metrics,
+    recorder: Recorder,
+    logs: LogsConfig,
+    redactor: Redactor,
+    allow_broad_applier_role: bool,
+    require_service_account_name: bool,
 }
 
 impl Context {
@@ -68,35 +135,83 @@ impl Context {
     pub fn render_step_image(&self) -> String {
         self.kubectl_image_render.clone()
     }
+
+    fn config_map_key(&self) -> &str {
+        self.config_map
+            .as_ref()
+            .map_or(DEFAULT_CONFIG_MAP_KEY, |source| source.key.as_str())
+    }
+}
+
+/// Selects the ConfigMaps defining the instances in ConfigMap mode.
+#[derive(Debug, Clone)]
+pub struct ConfigMapSource {
+    /// Label selector of the ConfigMaps; when unset, only the ConfigMap named `name` is selected.
+    pub selector: Option<String>,
+    pub name: String,
+    /// Key of the ConfigMap data holding the AppInstance.
+    pub key: String,
+}
+
+impl ConfigMapSource {
+    /// Selects the ConfigMaps server-side, so that the controller doesn't see the other ones.
+    fn watcher_config(&self) -> watcher::Config {
+        let config = watcher::Config::default().any_semantic();
+        match &self.selector {
+            Some(selector) => config.labels(selector),
+            None => config.fields(&format!("metadata.name={}", self.name)),
+        }
+    }
 }
 
 fn error_policy_app_instance(
     app_instance: Arc<AppInstance>,
     error: &Error,
-    _ctx: Arc<Context>,
+    ctx: Arc<Context>,
 ) -> Action {
-    let name = app_instance.name_any();
-    warn!(?name, %error, "reconcile failed");
-    // TODO(mkm): make error requeue duration configurable
-    Action::requeue(Duration::from_secs(5))
+    error_policy(AppInstanceLike::from(app_instance), error, ctx)
 }
 
-fn error_policy_config_map(
-    config_map: Arc<ConfigMap>,
-    error: &Error,
-    _ctx: Arc<Context>,
-) -> Action {
-    let config = &config_map.as_ref().data.as_ref().unwrap()["app-instance"];
-    let app_instance: Result<AppInstance, _> = serde_yaml::from_str(config);
-    match app_instance {
-        Ok(ai) => error_policy_app_instance(Arc::new(ai), error, _ctx),
+fn error_policy_config_map(config_map: Arc<ConfigMap>, error: &Error, ctx: Arc<Context>) -> Action {
+    match AppInstanceLike::from_config_map(config_map.clone(), ctx.config_map_key()) {
+        Ok(ai) => error_policy(ai, error, ctx),
         Err(serr) => {
             warn!(%serr, "failed to convert config map to AppInstance while handling {}", error);
-            Action::requeue(Duration::from_secs(5))
+            let (_, delay) = ctx.backoff.failed(&backoff_key(config_map.as_ref()));
+            Action::requeue(delay)
         }
     }
 }
 
+fn error_policy(app_instance: AppInstanceLike, error: &Error, ctx: Arc<Context>) -> Action {
+    let name = app_instance.name_any();
+    warn!(?name, %error, "reconcile failed");
+
+    let (retry_count, delay) = ctx.backoff.failed(&app_instance.backoff_key());
+    info!(?name, retry_count, ?delay, "backing off");
+
+    // The error policy cannot wait for the status to be updated.
+    tokio::spawn(async move {
+        if let Err(error) = app_instance
+            .record_retry(&ctx, Some((retry_count, delay)))
+            .await
+        {
+            warn!(%error, "cannot record the retry in the status");
+        }
+    });
+
+    Action::requeue(delay)
+}
+
+/// Identifies the resource an instance is defined by, for the purpose of tracking its failures.
+fn backoff_key(resource: &impl Resource) -> String {
+    format!(
+        "{}/{}",
+        resource.namespace().unwrap_or_default(),
+        resource.name_any()
+    )
+}
+
 async fn reconcile_app_instance(
     app_instance: Arc<AppInstance>,
     ctx: Arc<Context>,
@@ -105,35 +220,39 @@ async fn reconcile_app_instance(
 }
 
 async fn reconcile_config_map(config_map: Arc<ConfigMap>, ctx: Arc<Context>) -> Result<Action> {
-    let cm_name = config_map.name_any();
-    if ctx
-        .as_ref()
-        .config_map_name
-        .as_ref()
-        .is_some_and(|x| x.as_str() == cm_name)
-    {
-        let app_instance = AppInstanceLike::from_config_map(config_map, "app-instance");
-        match app_instance {
-            Ok(ai) => reconcile(ai, ctx).await,
-            Err(error) => {
-                error!(%error, "failed to convert configmap to appinstance");
-                Err(Error::InvalidConfigMap(error.to_string()))
-            }
+    let app_instance = AppInstanceLike::from_config_map(config_map, ctx.config_map_key());
+    match app_instance {
+        Ok(ai) => reconcile(ai, ctx).await,
+        Err(error) => {
+            error!(%error, "failed to convert configmap to appinstance");
+            Err(Error::InvalidConfigMap(error.to_string()))
         }
-    } else {
-        Ok(Action::requeue(Duration::from_secs(5)))
     }
 }
 
 async fn reconcile(app_instance: AppInstanceLike, ctx: Arc<Context>) -> Result<Action> {
+    let (ns, name) = (
+        app_instance.instance.namespace_any(),
+        app_instance.name_any(),
+    );
+    let _in_progress = ctx.metrics.reconcile_started(&ns, &name);
+    let start = Instant::now();
+    let res = reconcile_instance(app_instance, ctx.clone()).await;
+    ctx.metrics.reconcile_finished(res.is_ok(), start.elapsed());
+    // Failures are always retried by the error policy.
+    let scheduled = res
+        .as_ref()
+        .map_or(true, |action| *action != Action::await_change());
+    ctx.metrics.reconcile_scheduled(&ns, &name, scheduled);
+    res
+}
+
+async fn reconcile_instance(app_instance: AppInstanceLike, ctx: Arc<Context>) -> Result<Action> {
     info!(
         name = app_instance.name_any(),
         namespace = app_instance.instance.namespace(),
         "--------------- Running reconciler ---------------"
     );
-    // slow down things a little bit
-    tokio::time::sleep(Duration::from_secs(1)).await;
-
     if app_instance.instance.spec.pause != ctx.only_paused {
         info!(
             name = app_instance.name_any(),
@@ -181,12 +300,12 @@ async fn reconcile(app_instance: AppInstanceLike, ctx: Arc<Context>) -> Result<A
                 |event| async {
                     match event {
                         Finalizer::Apply(cm) => {
-                            AppInstanceLike::from_config_map(cm, "app-instance")?
+                            AppInstanceLike::from_config_map(cm, ctx.config_map_key())?
                                 .reconcile_apply(&ctx)
                                 .await
                         }
                         Finalizer::Cleanup(cm) => {
-                            AppInstanceLike::from_config_map(cm, "app-instance")?
+                            AppInstanceLike::from_config_map(cm, ctx.config_map_key())?
                                 .reconcile_delete(&ctx)
                                 .await
                         }
@@ -202,94 +321,313 @@ async fn reconcile(app_instance: AppInstanceLike, ctx: Arc<Context>) -> Result<A
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
     client: Client,
-    kubecfg_image: String,
+    render_images: RenderImages,
     kubit_image: String,
     apply_step_image: String,
     render_step_image: String,
     only_paused: bool,
-    config_map_name: Option<String>,
-    watched_namespace: Option<String>,
+    config_map: Option<ConfigMapSource>,
+    watched_namespaces: Vec<String>,
+    namespace_selector: Option<String>,
+    default_job_template: JobTemplate,
+    backoff: BackoffConfig,
+    metrics: Metrics,
+    logs: LogsConfig,
+    redactor: Redactor,
+    allow_broad_applier_role: bool,
+    require_service_account_name: bool,
 ) -> Result<()> {
-    let namespace = watched_namespace.as_deref();
-
-    let jobs = if let Some(ns) = namespace {
-        Api::<Job>::namespaced(client.clone(), ns)
-    } else {
-        Api::<Job>::all(client.clone())
-    };
+    // The namespaces matching the selector get their controller as they come and go.
+    let static_namespaces = namespace_selector
+        .as_ref()
+        .map(|_| watched_namespaces.clone());
+    let namespaces =
+        select_namespaces(&client, watched_namespaces, namespace_selector.as_deref()).await?;
+    let static_namespaces = static_namespaces.or_else(|| namespaces.clone());
+
+    let recorder = Recorder::new(
+        client.clone(),
+        Reporter {
+            controller: "kubit".to_string(),
+            instance: std::env::var("HOSTNAME").ok(),
+        },
+    );
 
     info!("apply/delete image: {apply_step_image}");
     info!("render image: {render_step_image}");
 
-    if watched_namespace.is_none() {
-        info!("running kubit manager in AppInstance (CRD) mode");
-        let docs = if let Some(ns) = namespace {
-            Api::<AppInstance>::namespaced(client.clone(), ns)
-        } else {
-            Api::<AppInstance>::all(client.clone())
-        };
-        if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
-            error!("CRD is not queryable; {e:?}. Is the CRD installed?");
-            std::process::exit(1);
-        }
+    let ctx = Arc::new(Context {
+        client: client.clone(),
+        render_images,
+        kubit_image,
+        config_map: config_map.clone(),
+        only_paused,
+        default_job_template,
+        backoff: Backoff::new(backoff),
+        metrics,
+        recorder,
+        logs,
+        redactor,
+        allow_broad_applier_role,
+        require_service_account_name,
+        kubectl_image_apply: apply_step_image,
+        kubectl_image_render: render_step_image,
+    });
+
+    match config_map {
+        None => {
+            info!("running kubit manager in AppInstance (CRD) mode");
+            for docs in apis::<AppInstance>(&client, namespaces.as_deref()) {
+                if let Err(e) = docs.list(&ListParams::default().limit(1)).await {
+                    error!("CRD is not queryable; {e:?}. Is the CRD installed?");
+                    std::process::exit(1);
+                }
+            }
 
-        Controller::new(docs, watcher::Config::default().any_semantic())
-            .shutdown_on_signal()
-            .owns(jobs, watcher::Config::default().any_semantic())
-            .run(
+            if let Err(error) = sweep_cluster_rbac(&client, namespaces.as_deref()).await {
+                warn!(%error, "cannot delete the orphaned cluster RBAC");
+            }
+
+            run_controllers(
+                &client,
+                static_namespaces.as_deref(),
+                namespace_selector.as_deref(),
+                watcher::Config::default().any_semantic(),
                 reconcile_app_instance,
                 error_policy_app_instance,
-                Arc::new(Context {
-                    client,
-                    kubecfg_image,
-                    kubit_image,
-                    config_map_name: None,
-                    only_paused,
-                    kubectl_image_apply: apply_step_image,
-                    kubectl_image_render: render_step_image,
-                }),
+                ctx,
             )
-            .filter_map(|x| async move { std::result::Result::ok(x) })
-            .for_each(|_| futures::future::ready(()))
             .await;
-    } else {
-        info!("running kubit manager in ConfigMap, single-namespace mode");
-        let docs = if let Some(ns) = namespace {
-            Api::<ConfigMap>::namespaced(client.clone(), ns)
-        } else {
-            error!("ConfigMap configuration requires a namespace.");
-            std::process::exit(1);
-        };
+        }
+        Some(config_map) => {
+            info!("running kubit manager in ConfigMap mode");
+            if namespaces.is_none() {
+                error!("ConfigMap configuration requires a namespace.");
+                std::process::exit(1);
+            }
 
-        Controller::new(docs, watcher::Config::default().any_semantic())
-            .shutdown_on_signal()
-            .owns(jobs, watcher::Config::default().any_semantic())
-            .run(
+            run_controllers(
+                &client,
+                static_namespaces.as_deref(),
+                namespace_selector.as_deref(),
+                config_map.watcher_config(),
                 reconcile_config_map,
                 error_policy_config_map,
-                Arc::new(Context {
-                    client,
-                    kubecfg_image,
-                    kubit_image,
-                    config_map_name,
-                    only_paused,
-                    kubectl_image_apply: apply_step_image,
-                    kubectl_image_render: render_step_image,
-                }),
+                ctx,
             )
-            .filter_map(|x| async move { std::result::Result::ok(x) })
-            .for_each(|_| futures::future::ready(()))
             .await;
+        }
     }
 
     Ok(())
 }
 
+/// Returns the namespaces to watch, or `None` to watch all of them.
+///
+/// The namespaces matching the selector are the ones existing when the controller starts, which
+/// may be none yet.
+async fn select_namespaces(
+    client: &Client,
+    mut namespaces: Vec<String>,
+    selector: Option<&str>,
+) -> Result<Option<Vec<String>>> {
+    if let Some(selector) = selector {
+        let api: Api<Namespace> = Api::all(client.clone());
+        let selected = api
+            .list_metadata(&ListParams::default().labels(selector))
+            .await?;
+        namespaces.extend(selected.items.iter().map(|ns| ns.name_any()));
+    } else if namespaces.is_empty() {
+        return Ok(None);
+    }
+
+    namespaces.sort();
+    namespaces.dedup();
+    info!(?namespaces, "watching namespaces");
+    Ok(Some(namespaces))
+}
+
+/// Returns an API per watched namespace, or one for all namespaces.
+fn apis<K>(client: &Client, namespaces: Option<&[String]>) -> Vec<Api<K>>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>,
+{
+    match namespaces {
+        Some(namespaces) => namespaces
+            .iter()
+            .map(|ns| Api::namespaced(client.clone(), ns))
+            .collect(),
+        None => vec![Api::all(client.clone())],
+    }
+}
+
+/// Runs a controller per watched namespace, so that only namespaced permissions are needed
+/// when the namespaces are listed, until all of them shut down.
+///
+/// The namespaces matching `namespace_selector` are watched for as long as they match it.
+async fn run_controllers<K, F>(
+    client: &Client,
+    namespaces: Option<&[String]>,
+    namespace_selector: Option<&str>,
+    watcher_config: watcher::Config,
+    reconcile: fn(Arc<K>, Arc<Context>) -> F,
+    error_policy: fn(Arc<K>, &Error, Arc<Context>) -> Action,
+    ctx: Arc<Context>,
+) where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + Clone
+        + DeserializeOwned
+        + std::fmt::Debug
+        + Send
+        + Sync
+        + 'static,
+    F: Future<Output = Result<Action>> + Send + 'static,
+{
+    let controller = |docs: Api<K>, jobs: Api<Job>| {
+        Controller::new(docs, watcher_config.clone())
+            .shutdown_on_signal()
+            .owns(jobs, watcher::Config::default().any_semantic())
+    };
+    let run = |controller: Controller<K>| {
+        controller
+            .run(reconcile, error_policy, ctx.clone())
+            .for_each(|_| futures::future::ready(()))
+            .boxed()
+    };
+
+    let mut controllers: FuturesUnordered<_> = apis::<K>(client, namespaces)
+        .into_iter()
+        .zip(apis::<Job>(client, namespaces))
+        .map(|(docs, jobs)| run(controller(docs, jobs)))
+        .collect();
+
+    let Some(selector) = namespace_selector else {
+        while controllers.next().await.is_some() {}
+        return;
+    };
+
+    // Stops the controllers of the selected namespaces.
+    let mut selected: HashMap<String, oneshot::Sender<()>> = HashMap::new();
+    // Namespaces listed since the watch was (re)started.
+    let mut listed: Option<HashSet<String>> = None;
+    let mut events = watcher(
+        Api::<Namespace>::all(client.clone()),
+        watcher::Config::default().labels(selector),
+    )
+    .default_backoff()
+    .boxed();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        let event = tokio::select! {
+            _ = &mut shutdown => break,
+            Some(_) = controllers.next(), if !controllers.is_empty() => continue,
+            Some(event) = events.next() => event,
+        };
+        match event {
+            Ok(watcher::Event::Init) => listed = Some(HashSet::new()),
+            Ok(watcher::Event::Apply(ns) | watcher::Event::InitApply(ns)) => {
+                let name = ns.name_any();
+                if let Some(listed) = &mut listed {
+                    listed.insert(name.clone());
+                }
+                if selected.contains_key(&name)
+                    || namespaces.is_some_and(|namespaces| namespaces.contains(&name))
+                {
+                    continue;
+                }
+                info!(namespace = name, "watching selected namespace");
+                let (stop, stopped) = oneshot::channel();
+                controllers.push(run(controller(
+                    Api::namespaced(client.clone(), &name),
+                    Api::namespaced(client.clone(), &name),
+                )
+                .graceful_shutdown_on(stopped.map(|_| ()))));
+                selected.insert(name, stop);
+            }
+            Ok(watcher::Event::Delete(ns)) => {
+                if let Some(stop) = selected.remove(&ns.name_any()) {
+                    info!(namespace = ns.name_any(), "namespace not selected anymore");
+                    let _ = stop.send(());
+                }
+            }
+            Ok(watcher::Event::InitDone) => {
+                let listed = listed.take().unwrap_or_default();
+                let gone: Vec<_> = selected
+                    .keys()
+                    .filter(|name| !listed.contains(*name))
+                    .cloned()
+                    .collect();
+                for name in gone {
+                    info!(namespace = name, "namespace not selected anymore");
+                    let _ = selected.remove(&name).map(|stop| stop.send(()));
+                }
+            }
+            Err(error) => warn!(%error, "cannot watch the selected namespaces"),
+        }
+    }
+
+    // Let the reconciliations in progress finish.
+    while controllers.next().await.is_some() {}
+}
+
+/// Completes on Ctrl+C or SIGTERM, like the controllers shutting down on signal.
+async fn shutdown_signal() {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("cannot handle SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
 #[derive(Debug, Clone)]
 enum ReconciliationState {
     Idle,
     Executing,
-    JobTerminated(String, JobOutcome),
+    JobTerminated(String, JobOutcome, AppliedRevision, Option<Duration>),
+}
+
+/// What an apply job installed, as recorded in its annotations when it was launched.
+#[derive(Debug, Clone, Default)]
+struct AppliedRevision {
+    package_digest: Option<String>,
+    kubecfg_version: Option<String>,
+    generation: Option<i64>,
+    reconcile_requested_at: Option<String>,
+    completion_time: Option<Time>,
+}
+
+impl AppliedRevision {
+    fn from_job(job: &Job) -> Self {
+        let annotation = |key| job.annotations().get(key).cloned();
+        Self {
+            package_digest: annotation(PACKAGE_DIGEST_ANNOTATION),
+            kubecfg_version: annotation(KUBECFG_VERSION_ANNOTATION),
+            generation: annotation(GENERATION_ANNOTATION).and_then(|g| g.parse().ok()),
+            reconcile_requested_at: annotation(RECONCILE_REQUESTED_AT_ANNOTATION),
+            completion_time: job.status.as_ref().and_then(|s| s.completion_time.clone()),
+        }
+    }
+}
+
+/// Jobs that render the package before acting on the rendered manifests.
+#[derive(Debug, Clone, Copy)]
+enum RenderJob {
+    /// Applies the manifests.
+    Apply,
+    /// Diffs the manifests against the live objects, without changing anything.
+    DriftCheck,
+}
+
+impl RenderJob {
+    fn job_type(&self) -> &'static str {
+        match self {
+            RenderJob::Apply => "apply",
+            RenderJob::DriftCheck => "drift",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -325,10 +663,15 @@ impl AppInstanceLike {
     pub fn from_config_map(config_map: Arc<ConfigMap>, key: &str) -> Result<Self> {
         let config = &config_map.as_ref().data.as_ref();
         if let Some(config) = config {
-            let config = &config[key];
+            let config = config.get(key).ok_or_else(|| {
+                Error::InvalidConfigMap(format!("configmap did not have the `{key}` data key"))
+            })?;
             let mut ai: AppInstance =
                 serde_yaml::from_str(config).map_err(|e| Error::InvalidConfigMap(e.to_string()))?;
             ai.metadata.uid.clone_from(&config_map.metadata.uid);
+            // ConfigMaps have no generation; derive one from the embedded instance so that
+            // spec changes can be told apart from already applied ones.
+            ai.metadata.generation = Some(config_generation(config));
             Ok(Self {
                 original: AppInstanceLikeResources::ConfigMap(config_map),
                 instance: Arc::new(ai),
@@ -353,8 +696,32 @@ impl AppInstanceLike {
 
         let action = match state {
             ReconciliationState::Idle => {
-                match self.launch_job(ctx).await {
+                if let Some(resync_in) = self.applied_for(ctx).await? {
+                    let objects = self.applied_objects(ctx).await;
+                    let recheck = self.update_health(ctx, objects).await?.min(resync_in);
+                    let action = self.check_drift(ctx, recheck).await?;
+                    // Failures of the health or drift checks are transient, forget them once the
+                    // checks go through.
+                    ctx.backoff.reset(&self.backoff_key());
+                    self.record_retry(ctx, None).await?;
+                    return Ok(action);
+                }
+
+                if let Some(wait) = self.retry_wait(ctx).await? {
+                    info!(?wait, "waiting before retrying");
+                    return Ok(Action::requeue(wait));
+                }
+
+                match self.launch_job(ctx, RenderJob::Apply).await {
                     Ok(()) => {
+                        self.publish_event(
+                            ctx,
+                            EventType::Normal,
+                            "JobLaunched",
+                            "Apply",
+                            Some(format!("Launched job {}", self.job_name_for("apply"))),
+                        )
+                        .await;
                         self.update_condition(
                             ctx,
                             "Reconcilier",
@@ -364,7 +731,56 @@ impl AppInstanceLike {
                         )
                         .await?;
                     }
+                    Err(Error::InvalidSpec(errors)) => {
+                        self.publish_event(
+                            ctx,
+                            EventType::Warning,
+                            "RenderFailed",
+                            "Apply",
+                            Some(format!("Invalid spec: {}", errors.join(", "))),
+                        )
+                        .await;
+                        // Retrying won't help until the spec (or the package) changes.
+                        self.update_condition(ctx, "Reconcilier", "False", "Failed", None)
+                            .await?;
+                        self.update_condition(
+                            ctx,
+                            "Ready",
+                            "False",
+                            "InvalidSpec",
+                            Some(errors.join("\n")),
+                        )
+                        .await?;
+                        return Ok(Action::await_change());
+                    }
+                    Err(
+                        err @ (Error::UndeclaredPermissions | Error::ServiceAccountNameRequired),
+                    ) => {
+                        let reason = rejection_reason(&err);
+                        self.publish_event(
+                            ctx,
+                            EventType::Warning,
+                            reason,
+                            "Apply",
+                            Some(err.to_string()),
+                        )
+                        .await;
+                        // Retrying won't help until the package (or the controller flags) changes.
+                        self.update_condition(ctx, "Reconcilier", "False", "Failed", None)
+                            .await?;
+                        self.update_condition(ctx, "Ready", "False", reason, Some(err.to_string()))
+                            .await?;
+                        return Ok(Action::await_change());
+                    }
                     Err(err) => {
+                        self.publish_event(
+                            ctx,
+                            EventType::Warning,
+                            "JobLaunchFailed",
+                            "Apply",
+                            Some(format!("Cannot launch installation job: {err}")),
+                        )
+                        .await;
                         self.update_condition(ctx, "Reconcilier", "False", "Failed", None)
                             .await?;
 
@@ -388,12 +804,27 @@ impl AppInstanceLike {
                 );
                 Action::await_change()
             }
-            ReconciliationState::JobTerminated(job_uid, outcome) => {
-                let log_summary = self.capture_logs(ctx, job_uid).await?;
+            ReconciliationState::JobTerminated(job_uid, outcome, revision, duration) => {
+                let (log_summary, failed_container) = self.capture_logs(ctx, job_uid).await?;
+                ctx.metrics.apply_job_finished(
+                    &self.instance.namespace_any(),
+                    &self.name_any(),
+                    matches!(outcome, JobOutcome::Success),
+                    duration,
+                );
 
                 let action = match outcome {
                     JobOutcome::Success => {
                         info!("job completed successfully");
+                        self.publish_event(ctx, EventType::Normal, "ApplySucceeded", "Apply", None)
+                            .await;
+                        ctx.backoff.reset(&self.backoff_key());
+                        self.record_retry(ctx, None).await?;
+                        self.record_applied_revision(ctx, revision).await?;
+                        let objects = self.applied_objects(ctx).await;
+                        if let Some(objects) = &objects {
+                            self.update_inventory(ctx, objects).await?;
+                        }
                         self.update_condition(ctx, "Reconcilier", "True", "Succeeded", None)
                             .await?;
                         self.update_condition(
@@ -404,10 +835,23 @@ impl AppInstanceLike {
                             None,
                         )
                         .await?;
-                        Action::await_change()
+                        Action::requeue(self.update_health(ctx, objects).await?)
                     }
                     JobOutcome::Failure => {
                         info!("job failed");
+                        // The manifests are rendered by the init containers.
+                        let reason = match failed_container.as_deref() {
+                            Some(APPLY_CONTAINER) | None => "ApplyFailed",
+                            Some(_) => "RenderFailed",
+                        };
+                        self.publish_event(
+                            ctx,
+                            EventType::Warning,
+                            reason,
+                            "Apply",
+                            Some(log_summary.clone()),
+                        )
+                        .await;
                         self.update_condition(ctx, "Reconcilier", "True", "Failed", None)
                             .await?;
                         self.update_condition(
@@ -418,10 +862,14 @@ impl AppInstanceLike {
                             Some(log_summary),
                         )
                         .await?;
-                        Action::requeue(Duration::from_secs(60))
+
+                        let (retry_count, delay) = ctx.backoff.failed(&self.backoff_key());
+                        info!(retry_count, ?delay, "backing off");
+                        self.record_retry(ctx, Some((retry_count, delay))).await?;
+                        Action::requeue(delay)
                     }
                 };
-                self.delete_job(ctx).await?;
+                self.delete_job(ctx, "apply").await?;
                 action
             }
         };
@@ -435,13 +883,15 @@ impl AppInstanceLike {
             namespace = self.instance.namespace(),
             "Cleaning up!"
         );
+        ctx.metrics
+            .forget_instance(&self.instance.namespace_any(), &self.name_any());
         let jobs: Api<Job> = Api::namespaced(ctx.client.clone(), &self.instance.namespace_any());
         let apply_job_name = self.job_name_for("apply");
         let cleanup_job_name = self.job_name_for("cleanup");
 
         if let Some(apply_job) = jobs.get_opt(&apply_job_name).await? {
             info!("Deleting the running job");
-            self.delete_job(ctx).await?;
+            self.delete_job(ctx, "apply").await?;
 
             info!("Awaiting termination of {apply_job_name}");
             let job_uid = apply_job.uid().unwrap();
@@ -453,10 +903,20 @@ impl AppInstanceLike {
                 .await
                 .is_err()
             {
+                self.publish_event(
+                    ctx,
+                    EventType::Warning,
+                    "CleanupTimedOut",
+                    "Cleanup",
+                    Some(format!(
+                        "Timed out waiting for {apply_job_name} to be deleted"
+                    )),
+                )
+                .await;
                 return Err(Error::ResourceDeletionTimeout);
             } else {
                 self.create_cleanup(jobs, &cleanup_job_name, ctx).await?;
-                return self.delete_cleanup_hack_configmap(ctx).await;
+                return self.finish_cleanup(ctx).await;
             }
         }
 
@@ -464,10 +924,46 @@ impl AppInstanceLike {
         match jobs.get_opt(&cleanup_job_name).await? {
             Some(_) => {
                 self.create_cleanup(jobs, &cleanup_job_name, ctx).await?;
-                self.delete_cleanup_hack_configmap(ctx).await
+                self.finish_cleanup(ctx).await
             }
-            None => self.delete_cleanup_hack_configmap(ctx).await,
+            None => self.finish_cleanup(ctx).await,
+        }
+    }
+
+    async fn finish_cleanup(&self, ctx: &Context) -> Result<Action> {
+        let action = self.delete_cleanup_hack_configmap(ctx).await?;
+        self.delete_cluster_roles(ctx).await?;
+        ctx.backoff.reset(&self.backoff_key());
+        Ok(action)
+    }
+
+    /// Deletes the cluster-scoped RBAC objects created for the instance, together with the
+    /// ones shared by the instances of the namespace if it's the last one.
+    ///
+    /// They are not garbage collected, as cluster-scoped objects cannot be owned by an instance.
+    async fn delete_cluster_roles(&self, ctx: &Context) -> Result<()> {
+        if ctx.config_map.is_some() {
+            return Ok(());
+        }
+        let ns = self.instance.namespace_any();
+        let name = self.name_any();
+
+        delete_cluster_rbac(&ctx.client, &instance_cluster_rbac_name(&ns, &name)).await?;
+        delete_legacy_cluster_rbac(&ctx.client, &ns, &name).await?;
+
+        let instances: Api<AppInstance> = Api::namespaced(ctx.client.clone(), &ns);
+        let last = instances
+            .list_metadata(&ListParams::default())
+            .await?
+            .items
+            .iter()
+            .all(|instance| instance.name_any() == name);
+        if last {
+            delete_cluster_rbac(&ctx.client, &format!("{APPLIER_SERVICE_ACCOUNT}-crd-{ns}"))
+                .await?;
         }
+
+        Ok(())
     }
 
     /// Delete the ConfigMap that was used to prune the applyset.
@@ -484,10 +980,17 @@ impl AppInstanceLike {
     /// For further details see
     /// <https://kubernetes.io/docs/concepts/containers/container-lifecycle-hooks/#container-hooks>
     async fn delete_cleanup_hack_configmap(&self, ctx: &Context) -> Result<Action> {
-        let cm_api: Api<ConfigMap> =
-            Api::namespaced(ctx.client.clone(), &self.instance.namespace_any());
-        let delete_params = DeleteParams::default();
         let cm_name = &delete::cleanup_hack_resource_name(&self.name_any());
+        // Don't block the deletion of the instance on a target cluster that went away.
+        let client = match self.target_client(ctx).await {
+            Ok(client) => client,
+            Err(error) => {
+                warn!(%error, "cannot connect to the target cluster, leaving {cm_name} behind");
+                return Ok(Action::await_change());
+            }
+        };
+        let cm_api: Api<ConfigMap> = Api::namespaced(client, &self.instance.namespace_any());
+        let delete_params = DeleteParams::default();
         info!("Performing ConfigMap deletion on {cm_name} to finalise cleanup process.");
         cm_api
             .delete(cm_name, &delete_params)
@@ -510,10 +1013,48 @@ impl AppInstanceLike {
         job_name: &str,
         ctx: &Context,
     ) -> Result<Action> {
-        info!("Setting up RBAC");
-        self.setup_namespaced_roles(ctx).await?;
+        if self.instance.spec.service_account_name.is_some() {
+            info!("Using the service account of the instance");
+        } else if ctx.require_service_account_name {
+            // The package cannot have been applied without the service account.
+            let service_accounts: Api<ServiceAccount> =
+                Api::namespaced(ctx.client.clone(), &self.instance.namespace_any());
+            if service_accounts
+                .get_opt(APPLIER_SERVICE_ACCOUNT)
+                .await?
+                .is_none()
+            {
+                info!("No service account to run the cleanup job as, skipping it");
+                return Ok(Action::await_change());
+            }
+        } else {
+            info!("Setting up RBAC");
+            self.setup_service_account(ctx).await?;
+            // Keep the roles set up when the package was applied if it cannot be fetched anymore,
+            // or if the controller doesn't grant what it needs anymore: the instance must remain
+            // deletable either way.
+            match self.fetch_package_config(ctx).await {
+                Ok(package_config) => match self.setup_applier_roles(ctx, &package_config).await {
+                    Err(Error::UndeclaredPermissions) => {
+                        warn!("the package doesn't declare its permissions, keeping the applier roles")
+                    }
+                    res => res?,
+                },
+                Err(error) => {
+                    warn!(%error, "cannot fetch the package config, keeping the applier roles")
+                }
+            }
+        }
         info!("Creating cleanup job");
         self.launch_cleanup_job(ctx).await?;
+        self.publish_event(
+            ctx,
+            EventType::Normal,
+            "CleanupStarted",
+            "Cleanup",
+            Some(format!("Launched job {job_name}")),
+        )
+        .await;
 
         let cond = await_condition(jobs, job_name, is_job_completed());
         info!("Awaiting completion of {job_name}");
@@ -521,6 +1062,14 @@ impl AppInstanceLike {
             .await
             .is_err()
         {
+            self.publish_event(
+                ctx,
+                EventType::Warning,
+                "CleanupTimedOut",
+                "Cleanup",
+                Some(format!("Timed out waiting for {job_name} to complete")),
+            )
+            .await;
             Err(Error::ResourceDeletionTimeout)
         } else {
             info!("{job_name} deleted");
@@ -538,27 +1087,12 @@ impl AppInstanceLike {
             ..Default::default()
         }];
 
-        if let Some(ref refs) = self.instance.spec.image_pull_secrets {
-            let secret_ref = refs
-                .iter()
-                .exactly_one()
-                .map_err(|_| Error::UnsupportedMultipleImagePullSecrets)?;
-
-            let docker_creds = Volume {
-                name: "docker".to_string(),
-                secret: Some(SecretVolumeSource {
-                    secret_name: Some(secret_ref.name.clone()),
-                    items: Some(vec![KeyToPath {
-                        key: ".dockerconfigjson".to_string(),
-                        path: "config.json".to_string(),
-                        ..Default::default()
-                    }]),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            };
-            volumes.push(docker_creds);
+        // The cleanup doesn't need to pull the package, so don't let broken pull secrets block it.
+        match self.docker_config_volume(ctx).await {
+            Ok(volume) => volumes.extend(volume),
+            Err(error) => warn!(%error, "cannot setup docker config for the cleanup job"),
         }
+        volumes.extend(self.kubeconfig_volume());
 
         let mk_mount = |name: &str| VolumeMount {
             name: name.to_string(),
@@ -585,7 +1119,7 @@ impl AppInstanceLike {
         };
 
         let jobs: Api<Job> = Api::namespaced(ctx.client.clone(), ns);
-        let job = Job {
+        let mut job = Job {
             metadata: ObjectMeta {
                 name: Some(cleanup_job_name),
                 namespace: self.instance.namespace().clone(),
@@ -596,7 +1130,8 @@ impl AppInstanceLike {
                 backoff_limit: Some(0),
                 template: PodTemplateSpec {
                     spec: Some(PodSpec {
-                        service_account: Some(APPLIER_SERVICE_ACCOUNT.to_string()),
+                        service_account: Some(self.service_account().to_string()),
+                        image_pull_secrets: self.instance.spec.image_pull_secrets.clone(),
                         restart_policy: Some("Never".to_string()),
                         active_deadline_seconds: Some(180),
                         volumes: Some(volumes),
@@ -615,13 +1150,14 @@ impl AppInstanceLike {
                                         "/manifests/cm-{}",
                                         delete::cleanup_hack_resource_name(&self.name_any())
                                     ),
+                                    &delete::ClusterAccess::default(),
                                     false,
                                 )
                                 .join(" "),
                             ]),
                             ..container_defaults.clone()
                         }]),
-                        containers: vec![Container {
+                        containers: vec![self.targeting_cluster(Container {
                             name: "cleanup-manifests".to_string(),
                             image: Some(ctx.render_step_image()),
                             command: Some(delete::emit_commandline(
@@ -630,10 +1166,11 @@ impl AppInstanceLike {
                                     "/manifests/cm-{}",
                                     delete::cleanup_hack_resource_name(&self.name_any())
                                 ),
+                                &delete::ClusterAccess::default(),
                                 false,
                             )),
                             ..container_defaults.clone()
-                        }],
+                        })],
                         ..Default::default()
                     }),
                     ..Default::default()
@@ -642,6 +1179,7 @@ impl AppInstanceLike {
             }),
             ..Default::default()
         };
+        apply_job_template(&self.job_template(ctx), &mut job);
         let pp = PostParams::default();
 
         handle_resource_exists(jobs.create(&pp, &job).await)?;
@@ -665,10 +1203,12 @@ impl AppInstanceLike {
                 fn condition(job: &Job, cond: impl Condition<Job>) -> bool {
                     cond.matches_object(Some(job))
                 }
+                let revision = AppliedRevision::from_job(&job);
+                let duration = job_duration(&job);
                 if condition(&job, is_job_completed()) {
-                    ReconciliationState::JobTerminated(uid, JobOutcome::Success)
+                    ReconciliationState::JobTerminated(uid, JobOutcome::Success, revision, duration)
                 } else if condition(&job, is_job_failed()) {
-                    ReconciliationState::JobTerminated(uid, JobOutcome::Failure)
+                    ReconciliationState::JobTerminated(uid, JobOutcome::Failure, revision, duration)
                 } else {
                     ReconciliationState::Executing
                 }
@@ -678,46 +1218,154 @@ impl AppInstanceLike {
     }
 
     async fn get_image_pull_secrets(&self, ctx: &Context) -> Result<RegistryAuth> {
-        info!("getting image pull credentials");
+        package_auth(self.pull_secrets(ctx).await?, &self.instance)
+    }
 
-        let secret_name = {
-            let Some(ref refs) = self.instance.spec.image_pull_secrets else {
-                return Ok(RegistryAuth::Anonymous);
-            };
-            if refs.is_empty() {
-                return Ok(RegistryAuth::Anonymous);
-            }
-            refs.iter()
-                .exactly_one()
-                .map_err(|_| Error::UnsupportedMultipleImagePullSecrets)?
-                .name
-                .as_str()
+    /// Reads the image pull secrets, reporting the problems with them as events.
+    async fn pull_secrets(&self, ctx: &Context) -> Result<Option<DockerConfig>> {
+        let res = docker_config(&ctx.client, &self.instance).await;
+        if let Err(error) = &res {
+            self.publish_event(
+                ctx,
+                EventType::Warning,
+                "PullSecretFailed",
+                "ReadPullSecrets",
+                Some(error.to_string()),
+            )
+            .await;
+        }
+        res
+    }
+
+    async fn fetch_package_config(&self, ctx: &Context) -> Result<PackageConfig> {
+        let auth = self.get_image_pull_secrets(ctx).await?;
+
+        let start = Instant::now();
+        let res = oci::fetch_package_config(&self.instance, &auth).await;
+        let registry = self
+            .instance
+            .spec
+            .package
+            .image
+            .parse::<Reference>()
+            .map(|reference| reference.resolve_registry().to_string())
+            .unwrap_or_default();
+        ctx.metrics
+            .registry_fetched(&registry, res.is_ok(), start.elapsed());
+
+        Ok(res?)
+    }
+
+    /// Stores the merged content of the image pull secrets in a Secret owned by the instance
+    /// and returns the volume that projects it as the `config.json` used by the tools running
+    /// in the jobs.
+    ///
+    /// The Secret is deleted once the instance has no image pull secrets anymore.
+    async fn docker_config_volume(&self, ctx: &Context) -> Result<Option<Volume>> {
+        let ns = self.instance.namespace_any();
+        let secret_name = format!("kubit-docker-config-{}", self.name_any());
+        let secrets: Api<Secret> = Api::namespaced(ctx.client.clone(), &ns);
+
+        let Some(docker_config) = self.pull_secrets(ctx).await? else {
+            ignore_not_found(secrets.delete(&secret_name, &DeleteParams::default()).await)?;
+            return Ok(None);
         };
 
-        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
-        let secrets: Api<Secret> = Api::namespaced(ctx.client.clone(), ns);
-        let secret = secrets.get(secret_name).await?;
+        let secret = Secret {
+            metadata: ObjectMeta {
+                name: Some(secret_name.clone()),
+                namespace: Some(ns.clone()),
+                owner_references: self.owned_by(),
+                ..Default::default()
+            },
+            type_: Some(DOCKER_CONFIG_JSON_SECRET_TYPE.to_string()),
+            data: Some(BTreeMap::from([(
+                ".dockerconfigjson".to_string(),
+                ByteString(docker_config.to_vec()?),
+            )])),
+            ..Default::default()
+        };
+        secrets
+            .patch(&secret_name, &patch_params(), &Patch::Apply(&secret))
+            .await?;
 
-        if secret.type_ != Some("kubernetes.io/dockerconfigjson".to_string()) {
-            return Err(Error::BadImagePullSecretType(secret.type_));
-        }
+        Ok(Some(Volume {
+            name: "docker".to_string(),
+            secret: Some(SecretVolumeSource {
+                secret_name: Some(secret_name),
+                items: Some(vec![KeyToPath {
+                    key: ".dockerconfigjson".to_string(),
+                    path: "config.json".to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }))
+    }
 
-        let docker_config = secret
-            .data
-            .as_ref()
-            .and_then(|data| data.get(".dockerconfigjson"))
-            .ok_or(Error::NoDockerConfigJsonInImagePullSecret)?;
+    /// Mounts the kubeconfig of the target cluster, if any.
+    fn kubeconfig_volume(&self) -> Option<Volume> {
+        let secret_ref = &self
+            .instance
+            .spec
+            .target_cluster
+            .as_ref()?
+            .kubeconfig_secret_ref;
+        Some(Volume {
+            name: KUBECONFIG_VOLUME.to_string(),
+            secret: Some(SecretVolumeSource {
+                secret_name: Some(secret_ref.name.clone()),
+                items: Some(vec![KeyToPath {
+                    key: secret_ref.key().to_string(),
+                    path: "config".to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// Points a container acting on the installed resources at the target cluster, if any.
+    fn targeting_cluster(&self, mut container: Container) -> Container {
+        if self.instance.spec.target_cluster.is_some() {
+            container.env.get_or_insert_with(Vec::new).push(EnvVar {
+                name: "KUBECONFIG".to_string(),
+                value: Some(KUBECONFIG_PATH.to_string()),
+                ..Default::default()
+            });
+        }
+        container
+    }
 
-        let docker_config = DockerConfig::from_slice(&docker_config.0)?;
+    /// Returns a client for the cluster the package is installed in.
+    async fn target_client(&self, ctx: &Context) -> Result<Client> {
+        let Some(kubeconfig) = target_kubeconfig(&ctx.client, &self.instance).await? else {
+            return Ok(ctx.client.clone());
+        };
+        let config =
+            kube::Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default()).await?;
+        Ok(Client::try_from(config)?)
+    }
 
-        let reference: Reference = self.instance.spec.package.image.parse()?;
-        Ok(docker_config.get_auth(reference.registry())?)
+    /// The service account the jobs run as.
+    fn service_account(&self) -> &str {
+        self.instance
+            .spec
+            .service_account_name
+            .as_deref()
+            .unwrap_or(APPLIER_SERVICE_ACCOUNT)
     }
 
-    async fn fetch_package_config(&self, ctx: &Context) -> Result<PackageConfig> {
-        let auth = self.get_image_pull_secrets(ctx).await?;
-        let res = oci::fetch_package_config(&self.instance, &auth).await?;
-        Ok(res)
+    /// The job template of the instance, completed with the controller defaults.
+    fn job_template(&self, ctx: &Context) -> JobTemplate {
+        self.instance
+            .spec
+            .job_template
+            .clone()
+            .unwrap_or_default()
+            .or(&ctx.default_job_template)
     }
 
     fn owned_by(&self) -> Option<Vec<OwnerReference>> {
@@ -733,40 +1381,110 @@ impl AppInstanceLike {
         }
     }
 
-    async fn setup_cluster_roles(&self, ctx: &Context) -> Result<()> {
+    /// Grants the applier service account the permissions needed to install the package.
+    ///
+    /// Packages that declare their permissions get roles scoped to what they declared, the
+    /// others get broad permissions on the namespace and on CRDs, if the controller allows it.
+    async fn setup_applier_roles(
+        &self,
+        ctx: &Context,
+        package_config: &PackageConfig,
+    ) -> Result<()> {
         let ns = self.instance.namespace_any();
-        let pp = patch_params();
-        let crd_name = format!("{APPLIER_SERVICE_ACCOUNT}-crd-{ns}");
+        let config_map_mode = ctx.config_map.is_some();
+
+        // The package is installed with the credentials of the kubeconfig, the jobs only read
+        // the instance from this cluster.
+        if self.instance.spec.target_cluster.is_some() {
+            return self
+                .setup_namespaced_roles(
+                    ctx,
+                    &format!("{APPLIER_SERVICE_ACCOUNT}-{}", self.name_any()),
+                    vec![instance_rule(&self.name_any(), config_map_mode)],
+                )
+                .await;
+        }
 
-        let metadata = ObjectMeta {
-            name: Some(crd_name.clone()),
-            namespace: None,
-            owner_references: self.owned_by(),
+        match package_config.permissions()? {
+            Some(permissions) => {
+                self.setup_namespaced_roles(
+                    ctx,
+                    &format!("{APPLIER_SERVICE_ACCOUNT}-{}", self.name_any()),
+                    applier_rules(&self.name_any(), config_map_mode, &permissions.namespaced),
+                )
+                .await?;
+                if config_map_mode {
+                    if !permissions.cluster.is_empty() {
+                        warn!("cluster permissions are not granted in ConfigMap mode");
+                    }
+                } else if !permissions.cluster.is_empty() {
+                    self.setup_cluster_roles(
+                        ctx,
+                        &instance_cluster_rbac_name(&ns, &self.name_any()),
+                        permissions.cluster.iter().map(policy_rule).collect(),
+                        true,
+                    )
+                    .await?;
+                    delete_legacy_cluster_rbac(&ctx.client, &ns, &self.name_any()).await?;
+                }
+            }
+            None if ctx.allow_broad_applier_role => {
+                self.setup_namespaced_roles(
+                    ctx,
+                    APPLIER_SERVICE_ACCOUNT,
+                    vec![rule(&["*"], &["*"], &APPLIER_VERBS)],
+                )
+                .await?;
+                if !config_map_mode {
+                    self.setup_cluster_roles(
+                        ctx,
+                        &format!("{APPLIER_SERVICE_ACCOUNT}-crd-{ns}"),
+                        vec![rule(
+                            &["apiextensions.k8s.io"],
+                            &["customresourcedefinitions"],
+                            &["delete", "create", "patch", "list", "get"],
+                        )],
+                        false,
+                    )
+                    .await?;
+                }
+            }
+            None => return Err(Error::UndeclaredPermissions),
+        }
+
+        Ok(())
+    }
+
+    /// Sets up a ClusterRole for the applier, either specific to the instance or shared by the
+    /// instances of the namespace.
+    async fn setup_cluster_roles(
+        &self,
+        ctx: &Context,
+        name: &str,
+        rules: Vec<PolicyRule>,
+        per_instance: bool,
+    ) -> Result<()> {
+        let ns = self.instance.namespace_any();
+        let pp = patch_params();
+
+        // Cluster-scoped objects cannot be owned by a namespaced one, so they are tracked
+        // with labels and deleted explicitly.
+        let metadata = ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: None,
+            labels: Some(BTreeMap::from([
+                (MANAGED_BY_LABEL.to_string(), "kubit".to_string()),
+                (NAMESPACE_LABEL.to_string(), ns.clone()),
+            ])),
+            annotations: per_instance
+                .then(|| BTreeMap::from([(INSTANCE_ANNOTATION.to_string(), self.name_any())])),
             ..Default::default()
         };
 
         let role: Api<ClusterRole> = Api::all(ctx.client.clone());
         let res = ClusterRole {
             metadata: metadata.clone(),
-            rules: Some(vec![PolicyRule {
-                api_groups: Some(
-                    ["apiextensions.k8s.io"]
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect(),
-                ),
-                resources: Some(
-                    ["customresourcedefinitions"]
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect(),
-                ),
-                verbs: ["delete", "create", "patch", "list", "get"]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                ..Default::default()
-            }]),
+            rules: Some(rules),
             ..Default::default()
         };
         role.patch(&res.name_any(), &pp, &Patch::Apply(&res))
@@ -778,7 +1496,7 @@ impl AppInstanceLike {
             role_ref: RoleRef {
                 api_group: "rbac.authorization.k8s.io".to_string(),
                 kind: "ClusterRole".to_string(),
-                name: crd_name.clone(),
+                name: name.to_string(),
             },
             subjects: Some(vec![Subject {
                 kind: "ServiceAccount".to_string(),
@@ -793,40 +1511,46 @@ impl AppInstanceLike {
         Ok(())
     }
 
-    async fn setup_namespaced_roles(&self, ctx: &Context) -> Result<()> {
+    async fn setup_service_account(&self, ctx: &Context) -> Result<()> {
         let ns = self.instance.namespace_any();
-        let pp = patch_params();
-
-        let metadata = ObjectMeta {
-            name: Some(APPLIER_SERVICE_ACCOUNT.to_string()),
-            namespace: self.instance.namespace().clone(),
-            owner_references: self.owned_by(),
-            ..Default::default()
-        };
 
         let service_account: Api<ServiceAccount> = Api::namespaced(ctx.client.clone(), &ns);
         let res = ServiceAccount {
-            metadata: metadata.clone(),
+            metadata: ObjectMeta {
+                name: Some(APPLIER_SERVICE_ACCOUNT.to_string()),
+                namespace: self.instance.namespace().clone(),
+                owner_references: self.owned_by(),
+                ..Default::default()
+            },
             ..Default::default()
         };
         service_account
-            .patch(&res.name_any(), &pp, &Patch::Apply(&res))
+            .patch(&res.name_any(), &patch_params(), &Patch::Apply(&res))
             .await?;
 
+        Ok(())
+    }
+
+    async fn setup_namespaced_roles(
+        &self,
+        ctx: &Context,
+        name: &str,
+        rules: Vec<PolicyRule>,
+    ) -> Result<()> {
+        let ns = self.instance.namespace_any();
+        let pp = patch_params();
+
+        let metadata = ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: self.instance.namespace().clone(),
+            owner_references: self.owned_by(),
+            ..Default::default()
+        };
+
         let role: Api<Role> = Api::namespaced(ctx.client.clone(), &ns);
         let res = Role {
             metadata: metadata.clone(),
-            rules: Some(vec![PolicyRule {
-                api_groups: Some(["*"].iter().map(|s| s.to_string()).collect()),
-                resources: Some(["*"].iter().map(|s| s.to_string()).collect()),
-                verbs: [
-                    "create", "update", "get", "list", "patch", "watch", "delete",
-                ]
-                .iter()
-                .map(|s| s.to_string())
-                .collect(),
-                ..Default::default()
-            }]),
+            rules: Some(rules),
         };
         role.patch(&res.name_any(), &pp, &Patch::Apply(&res))
             .await?;
@@ -837,7 +1561,7 @@ impl AppInstanceLike {
             role_ref: RoleRef {
                 api_group: "rbac.authorization.k8s.io".to_string(),
                 kind: "Role".to_string(),
-                name: APPLIER_SERVICE_ACCOUNT.to_string(),
+                name: name.to_string(),
             },
             subjects: Some(vec![Subject {
                 kind: "ServiceAccount".to_string(),
@@ -851,29 +1575,51 @@ impl AppInstanceLike {
         Ok(())
     }
 
-    async fn launch_job(&self, ctx: &Context) -> Result<()> {
-        self.setup_namespaced_roles(ctx).await?;
-        if ctx.config_map_name.is_none() {
-            self.setup_cluster_roles(ctx).await?;
+    /// Tells whether the jobs run as the managed `kubit-applier` service account, which is
+    /// refused when the controller requires a `serviceAccountName`.
+    fn managed_service_account(&self, require_service_account_name: bool) -> Result<bool> {
+        let managed = self.instance.spec.service_account_name.is_none();
+        if managed && require_service_account_name {
+            return Err(Error::ServiceAccountNameRequired);
         }
+        Ok(managed)
+    }
+
+    async fn launch_job(&self, ctx: &Context, render_job: RenderJob) -> Result<()> {
+        let managed_service_account =
+            self.managed_service_account(ctx.require_service_account_name)?;
 
         let package_config: PackageConfig = self.fetch_package_config(ctx).await?;
         info!("got package config");
 
-        let kubecfg_image = package_config.versioned_kubecfg_image(&ctx.kubecfg_image)?;
-        info!("Using: {}", kubecfg_image);
+        let spec =
+            serde_json::to_value(&self.instance.spec.package.spec).map_err(Error::RenderOverlay)?;
+        let errors = package_config.validate_spec(&spec)?;
+        if !errors.is_empty() {
+            return Err(Error::InvalidSpec(errors));
+        }
+
+        if managed_service_account {
+            self.setup_service_account(ctx).await?;
+            self.setup_applier_roles(ctx, &package_config).await?;
+        }
+
+        let engine = RenderEngine::for_package(&package_config)?;
+        let render_image = engine.image(&package_config, &ctx.render_images)?;
+        info!(%engine, "Using: {}", render_image);
 
-        self.create_job(kubecfg_image, ctx).await
+        self.create_job(render_job, engine, render_image, &package_config, ctx)
+            .await
     }
 
     fn job_name_for(&self, job_type: &str) -> String {
         format!("kubit-{job_type}-{}", self.name_any())
     }
 
-    async fn delete_job(&self, ctx: &Context) -> Result<()> {
+    async fn delete_job(&self, ctx: &Context, job_type: &str) -> Result<()> {
         let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
         let jobs: Api<Job> = Api::namespaced(ctx.client.clone(), ns);
-        let name = self.job_name_for("apply");
+        let name = self.job_name_for(job_type);
         jobs.delete(
             &name,
             &DeleteParams {
@@ -886,9 +1632,41 @@ impl AppInstanceLike {
         Ok(())
     }
 
-    async fn create_job(&self, kubecfg_image: String, ctx: &Context) -> Result<()> {
+    async fn create_job(
+        &self,
+        render_job: RenderJob,
+        engine: RenderEngine,
+        render_image: String,
+        package_config: &PackageConfig,
+        ctx: &Context,
+    ) -> Result<()> {
         let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
-        let job_name = self.job_name_for("apply");
+        let job_name = self.job_name_for(render_job.job_type());
+
+        // Render exactly the package build whose digest gets recorded in the status,
+        // even if the tag is moved while the job is running.
+        let package_image = package_config.pinned_image(&self.instance.spec.package.image)?;
+
+        let mut annotations = BTreeMap::from([(
+            PACKAGE_DIGEST_ANNOTATION.to_string(),
+            package_config.digest().to_string(),
+        )]);
+        if let Some(kubecfg_version) = engine.kubecfg_version(package_config)? {
+            annotations.insert(KUBECFG_VERSION_ANNOTATION.to_string(), kubecfg_version);
+        }
+        if let Some(generation) = self.instance.metadata.generation {
+            annotations.insert(GENERATION_ANNOTATION.to_string(), generation.to_string());
+        }
+        if let Some(requested_at) = self
+            .instance
+            .annotations()
+            .get(RECONCILE_REQUESTED_AT_ANNOTATION)
+        {
+            annotations.insert(
+                RECONCILE_REQUESTED_AT_ANNOTATION.to_string(),
+                requested_at.clone(),
+            );
+        }
 
         let mut volumes = vec![
             Volume {
@@ -903,27 +1681,8 @@ impl AppInstanceLike {
             },
         ];
 
-        if let Some(ref refs) = self.instance.spec.image_pull_secrets {
-            let secret_ref = refs
-                .iter()
-                .exactly_one()
-                .map_err(|_| Error::UnsupportedMultipleImagePullSecrets)?;
-
-            let volume = Volume {
-                name: "docker".to_string(),
-                secret: Some(SecretVolumeSource {
-                    secret_name: Some(secret_ref.name.clone()),
-                    items: Some(vec![KeyToPath {
-                        key: ".dockerconfigjson".to_string(),
-                        path: "config.json".to_string(),
-                        ..Default::default()
-                    }]),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            };
-            volumes.push(volume);
-        }
+        volumes.extend(self.docker_config_volume(ctx).await?);
+        volumes.extend(self.kubeconfig_volume());
 
         let mk_mount = |name: &str| VolumeMount {
             name: name.to_string(),
@@ -949,43 +1708,58 @@ impl AppInstanceLike {
             ..Default::default()
         };
 
+        let main_container = match render_job {
+            RenderJob::Apply => Container {
+                name: APPLY_CONTAINER.to_string(),
+                image: Some(ctx.apply_step_image()),
+                command: Some(apply::emit_commandline(
+                    &self.instance,
+                    "/manifests",
+                    &None,
+                    false,
+                    &ctx.apply_step_image(),
+                )),
+                ..container_defaults.clone()
+            },
+            RenderJob::DriftCheck => Container {
+                name: DRIFT_CHECK_CONTAINER.to_string(),
+                // Like the cleanup job, this requires an image with a shell.
+                image: Some(ctx.apply_step_image()),
+                command: Some(apply::emit_diff_commandline(&self.instance, "/manifests")),
+                ..container_defaults.clone()
+            },
+        };
+
         let jobs: Api<Job> = Api::namespaced(ctx.client.clone(), ns);
-        let job = Job {
+        let mut job = Job {
             metadata: ObjectMeta {
                 name: Some(job_name),
                 namespace: self.instance.namespace().clone(),
                 owner_references: self.owned_by(),
+                annotations: Some(annotations),
                 ..Default::default()
             },
             spec: Some(JobSpec {
                 backoff_limit: Some(0),
                 template: PodTemplateSpec {
                     spec: Some(PodSpec {
-                        service_account: Some(APPLIER_SERVICE_ACCOUNT.to_string()),
+                        service_account: Some(self.service_account().to_string()),
+                        image_pull_secrets: self.instance.spec.image_pull_secrets.clone(),
                         restart_policy: Some("Never".to_string()),
                         active_deadline_seconds: Some(180),
                         volumes: Some(volumes),
                         init_containers: Some(
                             self.init_containers(
+                                ctx,
                                 ns,
-                                &kubecfg_image,
-                                &ctx.kubit_image,
+                                engine,
+                                &package_image,
+                                &render_image,
                                 &container_defaults,
                             )
                             .await,
                         ),
-                        containers: vec![Container {
-                            name: "apply-manifests".to_string(),
-                            image: Some(ctx.apply_step_image()),
-                            command: Some(apply::emit_commandline(
-                                &self.instance,
-                                "/manifests",
-                                &None,
-                                false,
-                                &ctx.apply_step_image(),
-                            )),
-                            ..container_defaults.clone()
-                        }],
+                        containers: vec![self.targeting_cluster(main_container)],
                         ..Default::default()
                     }),
                     ..Default::default()
@@ -994,6 +1768,7 @@ impl AppInstanceLike {
             }),
             ..Default::default()
         };
+        apply_job_template(&self.job_template(ctx), &mut job);
         let pp = PostParams::default();
 
         handle_resource_exists(jobs.create(&pp, &job).await)?;
@@ -1001,7 +1776,13 @@ impl AppInstanceLike {
         Ok(())
     }
 
-    async fn capture_logs(&self, ctx: &Context, job_uid: String) -> Result<String> {
+    /// Stores the logs of the apply job in the status and returns a summary of the logs of
+    /// the failed container, together with its name.
+    async fn capture_logs(
+        &self,
+        ctx: &Context,
+        job_uid: String,
+    ) -> Result<(String, Option<String>)> {
         let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
         info!(?ns, "reporting errors");
 
@@ -1017,6 +1798,9 @@ impl AppInstanceLike {
 
         let mut per_container_logs = HashMap::new();
         let mut log_summary = String::new();
+        let mut failed_container = None;
+
+        let redactor = self.log_redactor(ctx).await;
 
         // There should be exactly one pod per job. In the unlikely even
         // something is broken with k8s and we end up getting two pods matching the same job uid
@@ -1074,6 +1858,7 @@ impl AppInstanceLike {
                         },
                     )
                     .await?;
+                let logs = redactor.redact(&logs);
 
                 if Some(container_name) == failed_container_name.as_ref() {
                     if let Some(last_line) = logs.lines().next() {
@@ -1093,19 +1878,264 @@ impl AppInstanceLike {
             let logs_json =
                 serde_json::to_string(&per_container_logs).expect("cannot render basic json");
             info!(logs_json);
+            failed_container = failed_container.or(failed_container_name);
         }
 
         let old_status = self.old_status(ns, ctx).await?;
 
+        let full_logs = match self.store_logs(ctx, &job_uid, &per_container_logs).await {
+            Ok(full_logs) => Some(full_logs),
+            Err(error) => {
+                warn!(%error, "cannot store the full logs");
+                old_status.full_logs.clone()
+            }
+        };
+        let last_logs = per_container_logs
+            .iter()
+            .map(|(container, log)| {
+                let tail = logs::tail_lines(log, ctx.logs.tail_lines);
+                (container.clone(), tail.to_string())
+            })
+            .collect();
+
         self.update_status(
             ctx,
             AppInstanceStatus {
-                last_logs: Some(per_container_logs),
+                last_logs: Some(last_logs),
+                full_logs,
                 ..old_status
             },
         )
         .await?;
-        Ok(log_summary)
+        Ok((log_summary, failed_container))
+    }
+
+    /// Adds the full logs of a job to the history kept in the logs Secret of the instance.
+    async fn store_logs(
+        &self,
+        ctx: &Context,
+        job_uid: &str,
+        per_container_logs: &HashMap<String, String>,
+    ) -> Result<LogsReference> {
+        let ns = self.instance.namespace_any();
+        let secret_name = format!("kubit-logs-{}", self.name_any());
+        let secrets: Api<Secret> = Api::namespaced(ctx.client.clone(), &ns);
+
+        let mut history: BTreeMap<String, Vec<u8>> = secrets
+            .get_opt(&secret_name)
+            .await?
+            .and_then(|secret| secret.data)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect();
+        let key = logs::run_key(Utc::now(), job_uid);
+        logs::add_run(
+            &mut history,
+            key.clone(),
+            logs::render_run(per_container_logs),
+            ctx.logs.history,
+        );
+
+        let secret = Secret {
+            metadata: ObjectMeta {
+                name: Some(secret_name.clone()),
+                namespace: Some(ns),
+                owner_references: self.owned_by(),
+                ..Default::default()
+            },
+            data: Some(
+                history
+                    .into_iter()
+                    .map(|(key, value)| (key, ByteString(value)))
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        secrets
+            .patch(&secret_name, &patch_params(), &Patch::Apply(&secret))
+            .await?;
+
+        Ok(LogsReference { secret_name, key })
+    }
+
+    /// Returns the redactor of the logs of the jobs of the instance, which also hides the
+    /// credentials of its image pull secrets.
+    async fn log_redactor(&self, ctx: &Context) -> Redactor {
+        // Problems with the pull secrets are reported when launching the job.
+        let pull_secrets = docker_config(&ctx.client, &self.instance)
+            .await
+            .ok()
+            .flatten();
+        ctx.redactor.clone().with_values(
+            pull_secrets
+                .iter()
+                .flat_map(|docker_config| docker_config.secret_values()),
+        )
+    }
+
+    /// Returns the redacted logs of the given container of the pods of a job.
+    async fn job_logs(&self, ctx: &Context, job_name: &str, container: &str) -> Result<String> {
+        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        let pods_api: Api<Pod> = Api::namespaced(ctx.client.clone(), ns);
+        let pods = pods_api
+            .list(&ListParams::default().labels(&format!("job-name={job_name}")))
+            .await?;
+
+        let mut logs = String::new();
+        for pod in pods.items {
+            logs.push_str(
+                &pods_api
+                    .logs(
+                        &pod.name_any(),
+                        &LogParams {
+                            container: Some(container.to_string()),
+                            ..Default::default()
+                        },
+                    )
+                    .await?,
+            );
+        }
+        Ok(self.log_redactor(ctx).await.redact(&logs))
+    }
+
+    /// Runs the periodic drift check, if enabled, and reports its outcome in the Drifted condition.
+    async fn check_drift(&self, ctx: &Context, recheck: Duration) -> Result<Action> {
+        let Some(drift_detection) = &self.instance.spec.drift_detection else {
+            return Ok(Action::requeue(recheck));
+        };
+        let interval = match drift_detection.interval() {
+            Ok(interval) => interval,
+            Err(error) => {
+                self.update_condition(
+                    ctx,
+                    "Drifted",
+                    "Unknown",
+                    "InvalidInterval",
+                    Some(error.to_string()),
+                )
+                .await?;
+                return Ok(Action::requeue(recheck));
+            }
+        };
+
+        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        let jobs: Api<Job> = Api::namespaced(ctx.client.clone(), ns);
+        let job_type = RenderJob::DriftCheck.job_type();
+        let job_name = self.job_name_for(job_type);
+
+        let Some(job) = jobs.get_opt(&job_name).await? else {
+            let last_check = self.old_status(ns, ctx).await?.last_drift_check_time;
+            let elapsed = last_check.map(|t| (Utc::now() - t.0).to_std().unwrap_or_default());
+            return match elapsed {
+                Some(elapsed) if elapsed < interval => {
+                    Ok(Action::requeue(recheck.min(interval - elapsed)))
+                }
+                _ => {
+                    info!(job_name, "launching drift check");
+                    self.launch_job(ctx, RenderJob::DriftCheck).await?;
+                    Ok(Action::requeue(recheck))
+                }
+            };
+        };
+
+        let failed = is_job_failed().matches_object(Some(&job));
+        if !failed && !is_job_completed().matches_object(Some(&job)) {
+            info!(job_name, "waiting for drift check job execution");
+            return Ok(Action::requeue(recheck));
+        }
+
+        let logs = self.job_logs(ctx, &job_name, DRIFT_CHECK_CONTAINER).await?;
+        let old_status = self.old_status(ns, ctx).await?;
+        self.update_status(
+            ctx,
+            AppInstanceStatus {
+                last_drift_check_time: Some(Time(Utc::now())),
+                ..old_status
+            },
+        )
+        .await?;
+        self.delete_job(ctx, job_type).await?;
+
+        if failed {
+            self.update_condition(
+                ctx,
+                "Drifted",
+                "Unknown",
+                "DriftCheckFailed",
+                logs.lines().last().map(str::to_string),
+            )
+            .await?;
+            return Ok(Action::requeue(recheck));
+        }
+
+        let drifted = drift::parse_diff(&logs);
+        if drifted.is_empty() {
+            self.update_condition(ctx, "Drifted", "False", "InSync", None)
+                .await?;
+            return Ok(Action::requeue(recheck.min(interval)));
+        }
+
+        info!(?drifted, "drift detected");
+        self.update_condition(
+            ctx,
+            "Drifted",
+            "True",
+            "Drifted",
+            Some(drifted.iter().map(ToString::to_string).join("\n")),
+        )
+        .await?;
+
+        if drift_detection.auto_correct {
+            info!("re-applying the package to correct the drift");
+            self.launch_job(ctx, RenderJob::Apply).await?;
+            self.update_condition(ctx, "Reconcilier", "False", "ExpandingTemplate", None)
+                .await?;
+            return Ok(Action::await_change());
+        }
+        Ok(Action::requeue(recheck.min(interval)))
+    }
+
+    fn object_ref(&self) -> ObjectReference {
+        match &self.original {
+            AppInstanceLikeResources::AppInstance(app_instance) => app_instance.object_ref(&()),
+            AppInstanceLikeResources::ConfigMap(config_map) => config_map.object_ref(&()),
+        }
+    }
+
+    /// Publishes an event about the resource the instance is defined by.
+    ///
+    /// Failing to publish an event doesn't fail the reconciliation.
+    async fn publish_event(
+        &self,
+        ctx: &Context,
+        type_: EventType,
+        reason: &str,
+        action: &str,
+        note: Option<String>,
+    ) {
+        // The API server rejects notes longer than 1kB.
+        let note = note.map(|mut note| {
+            if note.len() > 1024 {
+                let mut end = 1021;
+                while !note.is_char_boundary(end) {
+                    end -= 1;
+                }
+                note.truncate(end);
+                note.push_str("...");
+            }
+            note
+        });
+        let event = Event {
+            type_,
+            reason: reason.to_string(),
+            note,
+            action: action.to_string(),
+            secondary: None,
+        };
+        if let Err(error) = ctx.recorder.publish(&event, &self.object_ref()).await {
+            warn!(%error, reason, "cannot publish event");
+        }
     }
 
     async fn update_condition(
@@ -1120,7 +2150,14 @@ impl AppInstanceLike {
         let old_status = self.old_status(ns, ctx).await?;
 
         let mut conditions = old_status.conditions;
-        update_condition_vec(&mut conditions, type_, status, reason, message)?;
+        update_condition_vec(
+            &mut conditions,
+            type_,
+            status,
+            reason,
+            message,
+            self.instance.metadata.generation,
+        )?;
 
         let status = AppInstanceStatus {
             conditions,
@@ -1130,6 +2167,228 @@ impl AppInstanceLike {
         self.update_status(ctx, status).await
     }
 
+    async fn record_applied_revision(
+        &self,
+        ctx: &Context,
+        revision: AppliedRevision,
+    ) -> Result<()> {
+        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        let old_status = self.old_status(ns, ctx).await?;
+
+        let status = AppInstanceStatus {
+            package_digest: revision.package_digest,
+            kubecfg_version: revision.kubecfg_version,
+            observed_generation: revision.generation,
+            last_reconcile_requested_at: revision.reconcile_requested_at,
+            last_applied_time: Some(revision.completion_time.unwrap_or(Time(Utc::now()))),
+            ..old_status
+        };
+
+        self.update_status(ctx, status).await
+    }
+
+    /// Returns the live objects installed by this instance.
+    ///
+    /// The inventory and the health are informative only, failing to list the objects must not
+    /// fail the installation.
+    async fn applied_objects(&self, ctx: &Context) -> Option<Vec<AppliedObject>> {
+        let client = match self.target_client(ctx).await {
+            Ok(client) => client,
+            Err(error) => {
+                warn!(%error, "cannot connect to the target cluster");
+                return None;
+            }
+        };
+        match inventory::applyset_objects(&client, &self.instance).await {
+            Ok(objects) => Some(objects),
+            Err(error) => {
+                warn!(%error, "cannot list the applyset objects");
+                None
+            }
+        }
+    }
+
+    async fn update_inventory(&self, ctx: &Context, objects: &[AppliedObject]) -> Result<()> {
+        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        let old_status = self.old_status(ns, ctx).await?;
+        self.update_status(
+            ctx,
+            AppInstanceStatus {
+                inventory: inventory::inventory(objects),
+                ..old_status
+            },
+        )
+        .await
+    }
+
+    /// Records the consecutive failures of the instance and when it'll be retried,
+    /// or clears them if `retry` is `None`.
+    async fn record_retry(&self, ctx: &Context, retry: Option<(u32, Duration)>) -> Result<()> {
+        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        let old_status = self.old_status(ns, ctx).await?;
+
+        let (retry_count, next_retry_time, retry_generation, retry_reconcile_requested_at) =
+            match retry {
+                Some((count, delay)) => (
+                    Some(count),
+                    Some(Time(Utc::now() + delay)),
+                    self.instance.metadata.generation,
+                    self.reconcile_requested_at().cloned(),
+                ),
+                None if old_status.retry_count.is_none()
+                    && old_status.next_retry_time.is_none() =>
+                {
+                    return Ok(());
+                }
+                None => (None, None, None, None),
+            };
+
+        self.update_status(
+            ctx,
+            AppInstanceStatus {
+                retry_count,
+                next_retry_time,
+                retry_generation,
+                retry_reconcile_requested_at,
+                ..old_status
+            },
+        )
+        .await
+    }
+
+    /// Returns how long to wait before retrying an instance that failed.
+    ///
+    /// The failures of previous generations, or from before a reconciliation was requested,
+    /// are forgotten so that fixing the spec takes effect right away.
+    async fn retry_wait(&self, ctx: &Context) -> Result<Option<Duration>> {
+        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        let old_status = self.old_status(ns, ctx).await?;
+        if old_status.retry_count.is_some()
+            && !retrying_same_attempt(
+                &old_status,
+                self.instance.metadata.generation,
+                self.reconcile_requested_at(),
+            )
+        {
+            info!("instance changed since the last failure, retrying now");
+            ctx.backoff.reset(&self.backoff_key());
+            return Ok(None);
+        }
+        Ok(old_status
+            .next_retry_time
+            .and_then(|t| (t.0 - Utc::now()).to_std().ok())
+            .filter(|wait| !wait.is_zero()))
+    }
+
+    fn reconcile_requested_at(&self) -> Option<&String> {
+        self.instance
+            .annotations()
+            .get(RECONCILE_REQUESTED_AT_ANNOTATION)
+    }
+
+    fn backoff_key(&self) -> String {
+        match &self.original {
+            AppInstanceLikeResources::AppInstance(ai) => backoff_key(ai.as_ref()),
+            AppInstanceLikeResources::ConfigMap(cm) => backoff_key(cm.as_ref()),
+        }
+    }
+
+    /// Returns `None` if the instance must be applied now, otherwise how long until it's due
+    /// for its periodic resync (`Duration::MAX` if it has none).
+    ///
+    /// An instance must be applied when its current generation hasn't been successfully applied
+    /// yet, or when a reconciliation has been requested through the annotation.
+    async fn applied_for(&self, ctx: &Context) -> Result<Option<Duration>> {
+        let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        let old_status = self.old_status(ns, ctx).await?;
+
+        if old_status.observed_generation.is_none()
+            || old_status.observed_generation != self.instance.metadata.generation
+        {
+            return Ok(None);
+        }
+        if self
+            .instance
+            .annotations()
+            .get(RECONCILE_REQUESTED_AT_ANNOTATION)
+            .is_some_and(|requested| {
+                old_status.last_reconcile_requested_at.as_ref() != Some(requested)
+            })
+        {
+            info!("reconciliation requested through annotation");
+            return Ok(None);
+        }
+
+        let resync_interval = match self.instance.spec.resync_interval() {
+            Ok(Some(interval)) => interval,
+            Ok(None) => return Ok(Some(Duration::MAX)),
+            Err(error) => {
+                warn!(%error, "ignoring invalid resync interval");
+                return Ok(Some(Duration::MAX));
+            }
+        };
+        let since_applied = old_status
+            .last_applied_time
+            .and_then(|t| (Utc::now() - t.0).to_std().ok())
+            .unwrap_or_default();
+        if since_applied >= resync_interval {
+            info!(?resync_interval, "resync interval elapsed");
+            return Ok(None);
+        }
+        Ok(Some(resync_interval - since_applied))
+    }
+
+    /// Updates the Healthy condition and returns when the health should be assessed again.
+    async fn update_health(
+        &self,
+        ctx: &Context,
+        objects: Option<Vec<AppliedObject>>,
+    ) -> Result<Duration> {
+        let Some(objects) = objects else {
+            self.update_condition(
+                ctx,
+                "Healthy",
+                "Unknown",
+                "Unknown",
+                Some("Cannot list the installed resources".to_string()),
+            )
+            .await?;
+            return Ok(UNHEALTHY_RECHECK_INTERVAL);
+        };
+
+        let health = health::assess(&objects);
+        info!(?health, "health assessed");
+        match health {
+            Health::Healthy => {
+                self.update_condition(ctx, "Healthy", "True", "Healthy", None)
+                    .await?;
+                Ok(HEALTHY_RECHECK_INTERVAL)
+            }
+            Health::Progressing(messages) => {
+                self.update_condition(
+                    ctx,
+                    "Healthy",
+                    "False",
+                    "Progressing",
+                    Some(messages.join("\n")),
+                )
+                .await?;
+                Ok(UNHEALTHY_RECHECK_INTERVAL)
+            }
+            Health::Degraded(messages) => {
+                self.update_condition(
+                    ctx,
+                    "Healthy",
+                    "False",
+                    "Degraded",
+                    Some(messages.join("\n")),
+                )
+                .await?;
+                Ok(UNHEALTHY_RECHECK_INTERVAL)
+            }
+        }
+    }
+
     async fn old_status(&self, ns: &str, ctx: &Context) -> Result<AppInstanceStatus> {
         match self.original {
             AppInstanceLikeResources::AppInstance(_) => {
@@ -1161,6 +2420,7 @@ impl AppInstanceLike {
 
     async fn update_status(&self, ctx: &Context, status: AppInstanceStatus) -> Result<()> {
         let ns = &self.instance.namespace().ok_or(Error::NamespaceRequired)?;
+        ctx.metrics.observe_status(ns, &self.name_any(), &status);
 
         match self.original {
             AppInstanceLikeResources::AppInstance(_) => {
@@ -1202,9 +2462,11 @@ impl AppInstanceLike {
 
     async fn init_containers(
         &self,
+        ctx: &Context,
         ns: &str,
-        kubecfg_image: &str,
-        kubit_image: &str,
+        engine: RenderEngine,
+        package_image: &str,
+        render_image: &str,
         container_defaults: &Container,
     ) -> Vec<Container> {
         let (command, name) = match self.original {
@@ -1212,6 +2474,7 @@ impl AppInstanceLike {
                 render::emit_fetch_app_instance_commandline(
                     ns,
                     &self.name_any(),
+                    engine,
                     "/overlay/appinstance.json",
                 ),
                 "fetch-app-instance",
@@ -1220,6 +2483,8 @@ impl AppInstanceLike {
                 render::emit_fetch_appinstance_from_config_map_commandline(
                     ns,
                     &self.name_any(),
+                    ctx.config_map_key(),
+                    engine,
                     "/overlay/appinstance.json",
                 ),
                 "fetch-config-map",
@@ -1227,29 +2492,410 @@ impl AppInstanceLike {
         };
         let fetch_container = Container {
             name: name.to_string(),
-            image: Some(kubit_image.to_string()),
+            image: Some(ctx.kubit_image.clone()),
             command: Some(command),
             ..container_defaults.clone()
         };
-        vec![
-            fetch_container,
-            Container {
+        let mut pinned_instance = (*self.instance).clone();
+        pinned_instance.spec.package.image = package_image.to_string();
+        let fetch_package_container = engine
+            .fetch_commandline(&pinned_instance, "/overlay/appinstance.json", false)
+            .map(|command| Container {
+                name: "fetch-package".to_string(),
+                image: Some(ctx.kubit_image.clone()),
+                command: Some(command),
+                ..container_defaults.clone()
+            });
+        [fetch_container]
+            .into_iter()
+            .chain(fetch_package_container)
+            .chain([Container {
                 name: "render-manifests".to_string(),
-                image: Some(kubecfg_image.to_string()),
+                image: Some(render_image.to_string()),
                 command: Some(
-                    render::emit_commandline(
-                        &self.instance,
-                        "/overlay/appinstance.json",
-                        Some("/manifests"),
-                        false,
-                        false,
-                        kubecfg_image.to_string(),
-                    )
-                    .await,
+                    engine
+                        .emit_commandline(
+                            &pinned_instance,
+                            "/overlay/appinstance.json",
+                            Some("/manifests"),
+                            false,
+                            false,
+                            &ctx.render_images,
+                        )
+                        .await,
                 ),
                 ..container_defaults.clone()
-            },
-        ]
+            }])
+            .collect()
+    }
+}
+
+/// Returns the credentials needed to pull the package image, as found in the
+/// image pull secrets referenced by the AppInstance.
+pub(crate) async fn registry_auth(
+    client: &Client,
+    app_instance: &AppInstance,
+) -> Result<RegistryAuth> {
+    info!("getting image pull credentials");
+    package_auth(docker_config(client, app_instance).await?, app_instance)
+}
+
+/// Picks the credentials for the registry hosting the package image.
+fn package_auth(
+    docker_config: Option<DockerConfig>,
+    app_instance: &AppInstance,
+) -> Result<RegistryAuth> {
+    let Some(docker_config) = docker_config else {
+        return Ok(RegistryAuth::Anonymous);
+    };
+
+    let reference: Reference = app_instance.spec.package.image.parse()?;
+    Ok(docker_config.get_auth(reference.registry())?)
+}
+
+/// Returns the content of all the image pull secrets referenced by the AppInstance merged
+/// together, or `None` if it doesn't reference any.
+///
+/// When more than one secret holds credentials for the same registry, the first listed wins.
+async fn docker_config(
+    client: &Client,
+    app_instance: &AppInstance,
+) -> Result<Option<DockerConfig>> {
+    let refs = app_instance
+        .spec
+        .image_pull_secrets
+        .as_deref()
+        .unwrap_or_default();
+    if refs.is_empty() {
+        return Ok(None);
+    }
+
+    let ns = &app_instance.namespace().ok_or(Error::NamespaceRequired)?;
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), ns);
+
+    let mut merged = DockerConfig::default();
+    for secret_ref in refs {
+        let secret = secrets.get(&secret_ref.name).await?;
+
+        if secret.type_.as_deref() != Some(DOCKER_CONFIG_JSON_SECRET_TYPE) {
+            return Err(Error::BadImagePullSecretType(secret.type_));
+        }
+
+        let docker_config = secret
+            .data
+            .as_ref()
+            .and_then(|data| data.get(".dockerconfigjson"))
+            .ok_or(Error::NoDockerConfigJsonInImagePullSecret)?;
+
+        merged.merge(DockerConfig::from_slice(&docker_config.0)?);
+    }
+
+    Ok(Some(merged))
+}
+
+/// Returns the kubeconfig of the cluster the AppInstance is installed in, or `None` if it is
+/// installed in the cluster the client talks to.
+pub async fn target_kubeconfig(
+    client: &Client,
+    app_instance: &AppInstance,
+) -> Result<Option<Kubeconfig>> {
+    let Some(target_cluster) = &app_instance.spec.target_cluster else {
+        return Ok(None);
+    };
+    let secret_ref = &target_cluster.kubeconfig_secret_ref;
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), &app_instance.namespace_any());
+    let secret = secrets.get(&secret_ref.name).await?;
+    let kubeconfig = secret
+        .data
+        .unwrap_or_default()
+        .remove(secret_ref.key())
+        .ok_or_else(|| {
+            Error::NoKubeconfigInSecret(secret_ref.name.clone(), secret_ref.key().to_string())
+        })?;
+    Ok(Some(Kubeconfig::from_yaml(&String::from_utf8_lossy(
+        &kubeconfig.0,
+    ))?))
+}
+
+/// Derives a generation for an AppInstance embedded in a ConfigMap from its serialized form.
+///
+/// It must not change across builds of the controller, lest every instance be re-applied after
+/// an upgrade, hence a SHA-256 digest rather than the standard library hasher.
+fn config_generation(config: &str) -> i64 {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(config.as_bytes());
+    let prefix: [u8; 8] = digest[..8]
+        .try_into()
+        .expect("SHA-256 digests are 32 bytes");
+    // Generations are positive.
+    (u64::from_be_bytes(prefix) & i64::MAX as u64) as i64
+}
+
+/// Overrides the fields of a job built by the controller with the ones set in the template.
+fn apply_job_template(template: &JobTemplate, job: &mut Job) {
+    let Some(job_spec) = job.spec.as_mut() else {
+        return;
+    };
+    if template.backoff_limit.is_some() {
+        job_spec.backoff_limit = template.backoff_limit;
+    }
+
+    let Some(pod_spec) = job_spec.template.spec.as_mut() else {
+        return;
+    };
+    if template.active_deadline_seconds.is_some() {
+        pod_spec.active_deadline_seconds = template.active_deadline_seconds;
+    }
+    if template.node_selector.is_some() {
+        pod_spec.node_selector.clone_from(&template.node_selector);
+    }
+    if template.tolerations.is_some() {
+        pod_spec.tolerations.clone_from(&template.tolerations);
+    }
+    if template.priority_class_name.is_some() {
+        pod_spec
+            .priority_class_name
+            .clone_from(&template.priority_class_name);
+    }
+    if template.security_context.is_some() {
+        pod_spec
+            .security_context
+            .clone_from(&template.security_context);
+    }
+
+    let containers = pod_spec
+        .init_containers
+        .iter_mut()
+        .flatten()
+        .chain(pod_spec.containers.iter_mut());
+    for container in containers {
+        if template.resources.is_some() {
+            container.resources.clone_from(&template.resources);
+        }
+        if template.container_security_context.is_some() {
+            container
+                .security_context
+                .clone_from(&template.container_security_context);
+        }
+    }
+}
+
+/// How long a terminated job ran for.
+fn job_duration(job: &Job) -> Option<Duration> {
+    let status = job.status.as_ref()?;
+    let start = status.start_time.as_ref()?;
+    let end = match &status.completion_time {
+        Some(completion_time) => completion_time,
+        None => status
+            .conditions
+            .iter()
+            .flatten()
+            .find(|c| c.type_ == "Failed" && c.status == "True")?
+            .last_transition_time
+            .as_ref()?,
+    };
+    (end.0 - start.0).to_std().ok()
+}
+
+/// Rules of the Role of the applier of a package that declares its permissions: the declared
+/// ones, plus the ones needed to read the instance and to maintain its applyset.
+fn applier_rules(
+    name: &str,
+    config_map_mode: bool,
+    declared: &[PermissionRule],
+) -> Vec<PolicyRule> {
+    let parent_verbs = ["get", "patch", "update", "delete"];
+
+    let mut rules = vec![
+        instance_rule(name, config_map_mode),
+        // The applyset parent.
+        named_rule("", "secrets", &parent_verbs, name.to_string()),
+        // The empty applyset applied to prune everything on deletion.
+        named_rule(
+            "",
+            "configmaps",
+            &parent_verbs,
+            delete::cleanup_hack_resource_name(name),
+        ),
+        // Creation cannot be restricted to resource names.
+        rule(&[""], &["secrets", "configmaps"], &["create"]),
+    ];
+    rules.extend(declared.iter().map(policy_rule));
+    rules
+}
+
+/// Allows reading the instance, to render the package with it.
+fn instance_rule(name: &str, config_map_mode: bool) -> PolicyRule {
+    if config_map_mode {
+        named_rule("", "configmaps", &["get"], name.to_string())
+    } else {
+        named_rule("kubecfg.dev", "appinstances", &["get"], name.to_string())
+    }
+}
+
+fn named_rule(
+    api_group: &str,
+    resource: &str,
+    verbs: &[&str],
+    resource_name: String,
+) -> PolicyRule {
+    PolicyRule {
+        resource_names: Some(vec![resource_name]),
+        ..rule(&[api_group], &[resource], verbs)
+    }
+}
+
+fn policy_rule(declared: &PermissionRule) -> PolicyRule {
+    PolicyRule {
+        api_groups: Some(declared.api_groups.clone()),
+        resources: Some(declared.resources.clone()),
+        verbs: declared
+            .verbs
+            .clone()
+            .unwrap_or_else(|| APPLIER_VERBS.iter().map(|s| s.to_string()).collect()),
+        ..Default::default()
+    }
+}
+
+fn rule(api_groups: &[&str], resources: &[&str], verbs: &[&str]) -> PolicyRule {
+    PolicyRule {
+        api_groups: Some(api_groups.iter().map(|s| s.to_string()).collect()),
+        resources: Some(resources.iter().map(|s| s.to_string()).collect()),
+        verbs: verbs.iter().map(|s| s.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+/// Deletes a ClusterRoleBinding and the ClusterRole with the same name, if they exist.
+async fn delete_cluster_rbac(client: &Client, name: &str) -> Result<()> {
+    let bindings: Api<ClusterRoleBinding> = Api::all(client.clone());
+    ignore_not_found(bindings.delete(name, &DeleteParams::default()).await)?;
+    let roles: Api<ClusterRole> = Api::all(client.clone());
+    ignore_not_found(roles.delete(name, &DeleteParams::default()).await)?;
+    info!(name, "cluster RBAC deleted");
+    Ok(())
+}
+
+/// Name of the ClusterRole and ClusterRoleBinding specific to an instance.
+///
+/// Joining the namespace and the name would be ambiguous (`a-b`/`c` and `a`/`b-c`), letting
+/// an instance take over the cluster permissions of another one, hence a digest of both.
+fn instance_cluster_rbac_name(ns: &str, name: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let digest = format!("{:x}", Sha256::digest(format!("{ns}/{name}").as_bytes()));
+    format!("{APPLIER_SERVICE_ACCOUNT}-{}", &digest[..16])
+}
+
+/// Deletes the cluster RBAC of an instance named after its namespace and name, as it was
+/// before [`instance_cluster_rbac_name`], unless it was last set up for another instance.
+async fn delete_legacy_cluster_rbac(client: &Client, ns: &str, name: &str) -> Result<()> {
+    let legacy_name = format!("{APPLIER_SERVICE_ACCOUNT}-{ns}-{name}");
+    let roles: Api<ClusterRole> = Api::all(client.clone());
+    let bindings: Api<ClusterRoleBinding> = Api::all(client.clone());
+    let owners = [
+        roles
+            .get_metadata_opt(&legacy_name)
+            .await?
+            .map(|role| role.metadata),
+        bindings
+            .get_metadata_opt(&legacy_name)
+            .await?
+            .map(|binding| binding.metadata),
+    ];
+    let owner = Some((ns.to_string(), Some(name.to_string())));
+    if owners
+        .iter()
+        .flatten()
+        .any(|metadata| applier_rbac_owner(metadata) == owner)
+    {
+        delete_cluster_rbac(client, &legacy_name).await?;
+    }
+    Ok(())
+}
+
+/// Deletes the cluster-scoped RBAC objects left behind by instances that no longer exist,
+/// e.g. deleted while the controller wasn't running.
+async fn sweep_cluster_rbac(client: &Client, namespaces: Option<&[String]>) -> Result<()> {
+    let mut instances = vec![];
+    for api in apis::<AppInstance>(client, namespaces) {
+        instances.extend(
+            api.list_metadata(&ListParams::default())
+                .await?
+                .items
+                .iter()
+                .map(|instance| {
+                    (
+                        instance.namespace().unwrap_or_default(),
+                        instance.name_any(),
+                    )
+                }),
+        );
+    }
+
+    let roles: Api<ClusterRole> = Api::all(client.clone());
+    let bindings: Api<ClusterRoleBinding> = Api::all(client.clone());
+    let mut orphans = vec![];
+    let role_metadata = roles.list_metadata(&ListParams::default()).await?.items;
+    let binding_metadata = bindings.list_metadata(&ListParams::default()).await?.items;
+    for metadata in role_metadata
+        .into_iter()
+        .map(|role| role.metadata)
+        .chain(binding_metadata.into_iter().map(|binding| binding.metadata))
+    {
+        let Some((ns, instance)) = applier_rbac_owner(&metadata) else {
+            continue;
+        };
+        // Leave the namespaces this controller doesn't watch alone.
+        if namespaces.is_some_and(|namespaces| !namespaces.contains(&ns)) {
+            continue;
+        }
+        let orphan = match instance {
+            Some(instance) => !instances.contains(&(ns, instance)),
+            None => !instances.iter().any(|(n, _)| *n == ns),
+        };
+        if orphan {
+            orphans.extend(metadata.name);
+        }
+    }
+
+    for name in orphans.into_iter().unique() {
+        info!(name, "deleting orphaned cluster RBAC");
+        delete_cluster_rbac(client, &name).await?;
+    }
+    Ok(())
+}
+
+/// Returns the namespace, and the instance if it's specific to one, that a cluster-scoped
+/// RBAC object was created for by the controller.
+fn applier_rbac_owner(metadata: &ObjectMeta) -> Option<(String, Option<String>)> {
+    let labels = metadata.labels.clone().unwrap_or_default();
+    if labels.get(MANAGED_BY_LABEL).map(String::as_str) == Some("kubit") {
+        let ns = labels.get(NAMESPACE_LABEL)?;
+        let instance = metadata
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(INSTANCE_ANNOTATION));
+        return Some((ns.clone(), instance.cloned()));
+    }
+
+    // Created before the objects were labeled, when they were (ineffectively) owned by an instance.
+    let owned_by_instance =
+        metadata.owner_references.iter().flatten().any(|owner| {
+            owner.kind == "AppInstance" && owner.api_version.starts_with("kubecfg.dev/")
+        });
+    let ns = metadata
+        .name
+        .as_deref()?
+        .strip_prefix(&format!("{APPLIER_SERVICE_ACCOUNT}-crd-"))?;
+    owned_by_instance.then(|| (ns.to_string(), None))
+}
+
+fn ignore_not_found<R>(res: kube::Result<R>) -> Result<()> {
+    match res {
+        Err(kube::Error::Api(ErrorResponse { code: 404, .. })) | Ok(_) => Ok(()),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -1299,6 +2945,7 @@ fn update_condition_vec(
     status: &str,
     reason: &str,
     message: Option<String>,
+    observed_generation: Option<i64>,
 ) -> Result<()> {
     let mut new_condition = AppInstanceCondition {
         message: message.unwrap_or_default(),
@@ -1306,7 +2953,7 @@ fn update_condition_vec(
         status: status.to_string(),
         type_: type_.to_string(),
         last_transition_time: Time(Utc::now()),
-        observed_generation: None,
+        observed_generation,
     };
     for i in vec.iter_mut() {
         if i.type_ == type_ {
@@ -1330,10 +2977,298 @@ fn find_condition(app_instance: &AppInstance, type_: &str) -> Option<AppInstance
         .and_then(|s| s.conditions.iter().find(|i| i.type_ == type_).cloned())
 }
 
+/// Reason of the Ready condition and of the event of an instance the controller refuses to apply.
+fn rejection_reason(err: &Error) -> &'static str {
+    match err {
+        Error::ServiceAccountNameRequired => "ServiceAccountNameRequired",
+        _ => "UndeclaredPermissions",
+    }
+}
+
+/// Tells whether the failures recorded in the status were for the current generation and
+/// reconciliation request of the instance.
+fn retrying_same_attempt(
+    status: &AppInstanceStatus,
+    generation: Option<i64>,
+    reconcile_requested_at: Option<&String>,
+) -> bool {
+    status.retry_generation == generation
+        && status.retry_reconcile_requested_at.as_ref() == reconcile_requested_at
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn cluster_rbac_names() {
+        let name = instance_cluster_rbac_name("a-b", "c");
+        assert_eq!(name, instance_cluster_rbac_name("a-b", "c"));
+        assert_ne!(name, instance_cluster_rbac_name("a", "b-c"));
+        assert!(name.starts_with("kubit-applier-"));
+        assert_eq!(name.len(), "kubit-applier-".len() + 16);
+    }
+
+    #[test]
+    fn cluster_rbac_owner() {
+        let labeled: ObjectMeta = serde_json::from_value(serde_json::json!({
+            "name": "kubit-applier-ns-foo",
+            "labels": { MANAGED_BY_LABEL: "kubit", NAMESPACE_LABEL: "ns" },
+            "annotations": { INSTANCE_ANNOTATION: "foo" },
+        }))
+        .unwrap();
+        assert_eq!(
+            applier_rbac_owner(&labeled),
+            Some(("ns".to_string(), Some("foo".to_string())))
+        );
+
+        let legacy: ObjectMeta = serde_json::from_value(serde_json::json!({
+            "name": "kubit-applier-crd-ns",
+            "ownerReferences": [{
+                "apiVersion": "kubecfg.dev/v1alpha1",
+                "kind": "AppInstance",
+                "name": "foo",
+                "uid": "1234",
+            }],
+        }))
+        .unwrap();
+        assert_eq!(applier_rbac_owner(&legacy), Some(("ns".to_string(), None)));
+
+        let other: ObjectMeta = serde_json::from_value(serde_json::json!({
+            "name": "kubit-applier-crd-ns",
+        }))
+        .unwrap();
+        assert_eq!(applier_rbac_owner(&other), None);
+    }
+
+    #[test]
+    fn config_map_key() {
+        let config_map: ConfigMap = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "foo", "namespace": "ns" },
+            "data": {
+                "instance.yaml": "metadata:\n  name: bar\nspec:\n  package:\n    image: ghcr.io/kubecfg/kubit/package-demo:v1\n    apiVersion: v1alpha1\n    spec: {}\n",
+            },
+        }))
+        .unwrap();
+        let config_map = Arc::new(config_map);
+
+        let instance =
+            AppInstanceLike::from_config_map(config_map.clone(), "instance.yaml").unwrap();
+        assert_eq!(instance.name_any(), "foo");
+        assert_eq!(
+            instance.instance.spec.package.image,
+            "ghcr.io/kubecfg/kubit/package-demo:v1"
+        );
+
+        assert!(matches!(
+            AppInstanceLike::from_config_map(config_map, DEFAULT_CONFIG_MAP_KEY),
+            Err(Error::InvalidConfigMap(_))
+        ));
+    }
+
+    #[test]
+    fn target_cluster() {
+        let mut instance: AppInstance = serde_json::from_value(serde_json::json!({
+            "apiVersion": "kubecfg.dev/v1alpha1",
+            "kind": "AppInstance",
+            "metadata": { "name": "foo", "namespace": "ns" },
+            "spec": {
+                "package": {
+                    "image": "ghcr.io/kubecfg/kubit/package-demo:v1",
+                    "apiVersion": "v1alpha1",
+                    "spec": {},
+                },
+            },
+        }))
+        .unwrap();
+        let local = AppInstanceLike::from(Arc::new(instance.clone()));
+        assert!(local.kubeconfig_volume().is_none());
+        assert!(local.targeting_cluster(Container::default()).env.is_none());
+
+        instance.spec.target_cluster = serde_json::from_value(serde_json::json!({
+            "kubeconfigSecretRef": { "name": "spoke-kubeconfig" },
+        }))
+        .unwrap();
+        let remote = AppInstanceLike::from(Arc::new(instance));
+        let secret = remote.kubeconfig_volume().unwrap().secret.unwrap();
+        assert_eq!(secret.secret_name.as_deref(), Some("spoke-kubeconfig"));
+        assert_eq!(secret.items.unwrap()[0].key, "value");
+        let env = remote.targeting_cluster(Container::default()).env.unwrap();
+        assert_eq!(env[0].name, "KUBECONFIG");
+        assert_eq!(env[0].value.as_deref(), Some(KUBECONFIG_PATH));
+    }
+
+    #[test]
+    fn applier_rules_from_permissions() {
+        let declared: Vec<PermissionRule> = serde_json::from_value(serde_json::json!([
+            { "apiGroups": ["apps"], "resources": ["deployments"] },
+            { "apiGroups": [""], "resources": ["services"], "verbs": ["get", "patch"] },
+        ]))
+        .unwrap();
+
+        let rules = applier_rules("foo", false, &declared);
+        assert_eq!(rules.len(), 6);
+        assert_eq!(
+            rules[0].resources.as_deref(),
+            Some(&["appinstances".to_string()][..])
+        );
+        assert_eq!(
+            rules[2].resource_names.as_deref(),
+            Some(&["foo-cleanup".to_string()][..])
+        );
+        assert_eq!(rules[4].verbs, APPLIER_VERBS);
+        assert_eq!(rules[5].verbs, ["get", "patch"]);
+        assert!(rules
+            .iter()
+            .all(|rule| rule.resources.as_ref().unwrap() != &["*"]));
+
+        let rules = applier_rules("foo", true, &declared);
+        assert_eq!(
+            rules[0].resources.as_deref(),
+            Some(&["configmaps".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn job_template() {
+        let defaults: JobTemplate = serde_yaml::from_str(
+            r#"
+            activeDeadlineSeconds: 600
+            priorityClassName: low
+            resources:
+              requests:
+                cpu: 100m
+            "#,
+        )
+        .unwrap();
+        let template = serde_yaml::from_str::<JobTemplate>(
+            r#"
+            priorityClassName: high
+            containerSecurityContext:
+              allowPrivilegeEscalation: false
+            "#,
+        )
+        .unwrap()
+        .or(&defaults);
+
+        let mut job = Job {
+            spec: Some(JobSpec {
+                backoff_limit: Some(0),
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        active_deadline_seconds: Some(180),
+                        init_containers: Some(vec![Container::default()]),
+                        containers: vec![Container::default()],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        apply_job_template(&template, &mut job);
+
+        let job_spec = job.spec.unwrap();
+        assert_eq!(job_spec.backoff_limit, Some(0));
+        let pod_spec = job_spec.template.spec.unwrap();
+        assert_eq!(pod_spec.active_deadline_seconds, Some(600));
+        assert_eq!(pod_spec.priority_class_name.as_deref(), Some("high"));
+        for container in pod_spec
+            .init_containers
+            .unwrap()
+            .iter()
+            .chain(pod_spec.containers.iter())
+        {
+            assert_eq!(container.resources, defaults.resources);
+            assert_eq!(
+                container
+                    .security_context
+                    .as_ref()
+                    .and_then(|c| c.allow_privilege_escalation),
+                Some(false)
+            );
+        }
+    }
+
+    #[test]
+    fn service_account_name_required() {
+        let mut app_instance: AppInstance = serde_yaml::from_str(
+            r#"
+apiVersion: kubecfg.dev/v1alpha1
+kind: AppInstance
+metadata:
+  name: foo
+  namespace: ns
+  generation: 1
+spec:
+  package:
+    image: ghcr.io/kubecfg/kubit/package-demo:v1
+    apiVersion: v1alpha1
+    spec: {}
+"#,
+        )
+        .unwrap();
+        let instance = |app_instance: &AppInstance| AppInstanceLike {
+            instance: Arc::new(app_instance.clone()),
+            original: AppInstanceLikeResources::AppInstance(Arc::new(app_instance.clone())),
+        };
+
+        assert!(instance(&app_instance)
+            .managed_service_account(false)
+            .unwrap());
+        let err = instance(&app_instance)
+            .managed_service_account(true)
+            .unwrap_err();
+        assert!(matches!(err, Error::ServiceAccountNameRequired));
+
+        let mut conditions = vec![];
+        update_condition_vec(
+            &mut conditions,
+            "Ready",
+            "False",
+            rejection_reason(&err),
+            Some(err.to_string()),
+            app_instance.metadata.generation,
+        )
+        .unwrap();
+        assert_eq!(conditions[0].reason, "ServiceAccountNameRequired");
+        assert_eq!(
+            rejection_reason(&Error::UndeclaredPermissions),
+            "UndeclaredPermissions"
+        );
+
+        app_instance.spec.service_account_name = Some("my-applier".to_string());
+        assert!(!instance(&app_instance)
+            .managed_service_account(true)
+            .unwrap());
+    }
+
+    #[test]
+    fn spec_change_retries_immediately() {
+        let status = AppInstanceStatus {
+            retry_count: Some(4),
+            next_retry_time: Some(Time(Utc::now() + Duration::from_secs(300))),
+            retry_generation: Some(2),
+            retry_reconcile_requested_at: None,
+            ..Default::default()
+        };
+        assert!(retrying_same_attempt(&status, Some(2), None));
+        assert!(!retrying_same_attempt(&status, Some(3), None));
+        assert!(!retrying_same_attempt(
+            &status,
+            Some(2),
+            Some(&"2026-10-17T10:00:00Z".to_string())
+        ));
+    }
+
+    #[test]
+    fn stable_config_generation() {
+        // Pinned, as a different value would re-apply all the ConfigMap-mode instances.
+        assert_eq!(config_generation("foo: bar"), 506968999568059526);
+        assert_ne!(config_generation("foo: baz"), config_generation("foo: bar"));
+    }
+
     #[test]
     fn manipulate_conditions() {
         let mut conditions = vec![];
@@ -1344,6 +3279,7 @@ mod tests {
             "False",
             "WakingUpWithoutCoffee",
             None,
+            None,
         )
         .unwrap();
 
@@ -1362,6 +3298,7 @@ mod tests {
             "False",
             "NotReady",
             Some("still waking up".to_string()),
+            None,
         )
         .unwrap();
 
@@ -1380,6 +3317,7 @@ mod tests {
             "True",
             "ReconciliationSucceeded",
             None,
+            None,
         )
         .unwrap();
 
@@ -1400,6 +3338,7 @@ mod tests {
             "True",
             "ReconciliationSucceeded",
             Some("message change doesn't cause transition time change".to_string()),
+            None,
         )
         .unwrap();
         let next_transition = conditions[0].last_transition_time.clone();
@@ -1414,11 +3353,13 @@ mod tests {
             "False",
             "EverythingIsBroken",
             None,
+            Some(2),
         )
         .unwrap();
 
         let next_transition = conditions[0].last_transition_time.clone();
 
         assert!(prev_transition < next_transition);
+        assert_eq!(conditions[0].observed_generation, Some(2));
     }
 }
@@ -9,17 +9,26 @@ use home::home_dir;
 use kube::ResourceExt;
 use std::env;
 
-pub fn emit_commandline(
-    app_instance: &AppInstance,
-    deletion_dir: &str,
-    docker: bool,
-) -> Vec<String> {
+/// How the deletion commands reach the cluster the resources are installed in, when it's not
+/// through the current kubeconfig context.
+#[derive(Clone, Debug, Default)]
+pub struct ClusterAccess {
+    /// Path of the kubeconfig of the cluster.
+    pub kubeconfig: Option<String>,
+    /// User to impersonate.
+    pub impersonate_user: Option<String>,
+}
+
+/// Command line running kubectl, either directly or in a container, with the flags selecting
+/// the cluster and the user to act as.
+fn kubectl(access: &ClusterAccess, docker: bool, docker_args: &[&str]) -> Vec<String> {
     let mut cli: Vec<String> = vec![];
 
     if docker {
-        let user_home = home_dir().expect("unable to retrieve home directory");
-        let kube_config =
-            env::var("KUBECONFIG").unwrap_or(format!("{}/.kube/config", user_home.display()));
+        let kube_config = access.kubeconfig.clone().unwrap_or_else(|| {
+            let user_home = home_dir().expect("unable to retrieve home directory");
+            env::var("KUBECONFIG").unwrap_or(format!("{}/.kube/config", user_home.display()))
+        });
         cli.extend(
             [
                 "docker",
@@ -30,28 +39,49 @@ pub fn emit_commandline(
                 "host",
                 "-v",
                 &format!("{}:/.kube/config", kube_config),
-                // The empty applyset must be mounted to be seen by the container.
-                "-v",
-                &format!("{}:{}", deletion_dir, deletion_dir),
-                "--env",
-                KUBECTL_APPLYSET_ENABLED,
+            ]
+            .iter()
+            .chain(docker_args)
+            .chain(&[
                 "--env",
                 "KUBECONFIG=/.kube/config",
                 DEFAULT_APPLY_KUBECTL_IMAGE,
-            ]
-            .iter()
+            ])
             .map(|s| s.to_string())
             .collect::<Vec<_>>(),
         );
     } else {
-        cli.extend(
-            ["kubectl"]
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>(),
-        );
+        cli.push("kubectl".to_string());
+        if let Some(kubeconfig) = &access.kubeconfig {
+            cli.push(format!("--kubeconfig={kubeconfig}"));
+        }
+    }
+
+    if let Some(as_user) = &access.impersonate_user {
+        cli.push(format!("--as={as_user}"));
     }
 
+    cli
+}
+
+pub fn emit_commandline(
+    app_instance: &AppInstance,
+    deletion_dir: &str,
+    access: &ClusterAccess,
+    docker: bool,
+) -> Vec<String> {
+    let mut cli = kubectl(
+        access,
+        docker,
+        &[
+            // The empty applyset must be mounted to be seen by the container.
+            "-v",
+            &format!("{}:{}", deletion_dir, deletion_dir),
+            "--env",
+            KUBECTL_APPLYSET_ENABLED,
+        ],
+    );
+
     cli.extend(
         [
             "apply",
@@ -79,40 +109,10 @@ pub fn emit_commandline(
 pub fn emit_post_deletion_commandline(
     app_instance: &AppInstance,
     name: &str,
+    access: &ClusterAccess,
     docker: bool,
 ) -> Vec<String> {
-    let mut cli: Vec<String> = vec![];
-
-    if docker {
-        let user_home = home_dir().expect("unable to retrieve home directory");
-        let kube_config =
-            env::var("KUBECONFIG").unwrap_or(format!("{}/.kube/config", user_home.display()));
-        cli.extend(
-            [
-                "docker",
-                "run",
-                "--interactive",
-                "--rm",
-                "--network",
-                "host",
-                "-v",
-                &format!("{}:/.kube/config", kube_config),
-                "--env",
-                "KUBECONFIG=/.kube/config",
-                DEFAULT_APPLY_KUBECTL_IMAGE,
-            ]
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>(),
-        );
-    } else {
-        cli.extend(
-            ["kubectl"]
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>(),
-        );
-    }
+    let mut cli = kubectl(access, docker, &[]);
 
     cli.extend(
         [
@@ -139,40 +139,10 @@ pub fn emit_deletion_setup(
     app_instance: &AppInstance,
     name: &str,
     output_path: &str,
+    access: &ClusterAccess,
     docker: bool,
 ) -> Vec<String> {
-    let mut cli: Vec<String> = vec![];
-
-    if docker {
-        let user_home = home_dir().expect("unable to retrieve home directory");
-        let kube_config =
-            env::var("KUBECONFIG").unwrap_or(format!("{}/.kube/config", user_home.display()));
-        cli.extend(
-            [
-                "docker",
-                "run",
-                "--interactive",
-                "--rm",
-                "--network",
-                "host",
-                "-v",
-                &format!("{}:/.kube/config", kube_config),
-                "--env",
-                "KUBECONFIG=/.kube/config",
-                DEFAULT_APPLY_KUBECTL_IMAGE,
-            ]
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>(),
-        );
-    } else {
-        cli.extend(
-            ["kubectl"]
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>(),
-        );
-    }
+    let mut cli = kubectl(access, docker, &[]);
 
     cli.extend(
         [
@@ -200,16 +170,26 @@ pub fn cleanup_hack_resource_name(name: &str) -> String {
 }
 
 /// Generates a shell script that will cleanup the created AppInstance resources.
-pub fn script(app_instance: &AppInstance, deletion_dir: &str, docker: bool) -> Result<Script> {
-    let tokens = emit_commandline(app_instance, deletion_dir, docker);
+pub fn script(
+    app_instance: &AppInstance,
+    deletion_dir: &str,
+    access: &ClusterAccess,
+    docker: bool,
+) -> Result<Script> {
+    let tokens = emit_commandline(app_instance, deletion_dir, access, docker);
     Ok(Script::from_vec(tokens))
 }
 
 /// Generates a shell script that is used post prune operation of the AppInstance
 /// resources. In other words, it is used to delete the blank ConfigMap that was
 /// used as the blank applyset.
-pub fn post_pruning_script(app_instance: &AppInstance, name: &str, docker: bool) -> Result<Script> {
-    let configmap_deletion = emit_post_deletion_commandline(app_instance, name, docker);
+pub fn post_pruning_script(
+    app_instance: &AppInstance,
+    name: &str,
+    access: &ClusterAccess,
+    docker: bool,
+) -> Result<Script> {
+    let configmap_deletion = emit_post_deletion_commandline(app_instance, name, access, docker);
     Ok(Script::from_vec(configmap_deletion))
 }
 
@@ -219,8 +199,65 @@ pub fn setup_script(
     app_instance: &AppInstance,
     name: &str,
     output_path: &str,
+    access: &ClusterAccess,
     docker: bool,
 ) -> Result<Script> {
-    let cleanup_helper = emit_deletion_setup(app_instance, name, output_path, docker);
+    let cleanup_helper = emit_deletion_setup(app_instance, name, output_path, access, docker);
     Ok(Script::from_vec(cleanup_helper))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arrange_app_instance() -> AppInstance {
+        let file = std::fs::File::open("tests/fixtures/fake-package.yml").unwrap();
+        serde_yaml::from_reader(file).unwrap()
+    }
+
+    #[test]
+    fn delete_emit_commandline() {
+        let app_instance = arrange_app_instance();
+
+        let output = emit_commandline(&app_instance, "/tmp/test", &ClusterAccess::default(), false);
+        assert_eq!(output[..2], ["kubectl", "apply"]);
+
+        let access = ClusterAccess {
+            kubeconfig: Some("/tmp/kubeconfig".to_string()),
+            impersonate_user: Some("alice".to_string()),
+        };
+        let output = emit_deletion_setup(&app_instance, "test", "/tmp/test/cm", &access, false);
+        assert_eq!(
+            output,
+            vec![
+                "kubectl",
+                "--kubeconfig=/tmp/kubeconfig",
+                "--as=alice",
+                "create",
+                "configmap",
+                "test-cleanup",
+                "--namespace",
+                "test",
+                "--dry-run=client",
+                "-o=yaml",
+                ">",
+                "/tmp/test/cm",
+            ]
+        );
+
+        let output = emit_post_deletion_commandline(&app_instance, "test", &access, true);
+        assert!(output.contains(&"/tmp/kubeconfig:/.kube/config".to_string()));
+        assert!(!output.iter().any(|arg| arg.starts_with("--kubeconfig")));
+        assert_eq!(
+            output[output.len() - 6..],
+            [
+                "--as=alice",
+                "delete",
+                "configmap",
+                "test-cleanup",
+                "--namespace",
+                "test"
+            ]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use base64::{engine::general_purpose, Engine as _};
 use oci_distribution::secrets::RegistryAuth;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -22,12 +22,12 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// A content of ~/.docker/config.json file which is also the same format
 /// of the contents of the kubernetes kubernetes.io/dockerconfigjson secret
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
 pub struct DockerConfig {
-    auths: HashMap<String, DockerCredentials>,
+    auths: BTreeMap<String, DockerCredentials>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum DockerCredentials {
     Split { username: String, password: String },
@@ -46,6 +46,34 @@ impl DockerConfig {
         Ok(serde_json::from_slice(data)?)
     }
 
+    /// Render the DockerConfig as JSON.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Adds the credentials of the registries that are not already present in this config,
+    /// i.e. the credentials merged first win.
+    pub fn merge(&mut self, other: DockerConfig) {
+        for (registry, credentials) in other.auths {
+            self.auths.entry(registry).or_insert(credentials);
+        }
+    }
+
+    /// Returns the passwords and the encoded credentials in this config, to keep them out of logs.
+    pub fn secret_values(&self) -> Vec<String> {
+        self.auths
+            .values()
+            .flat_map(|credentials| match credentials {
+                DockerCredentials::Split { password, .. } => vec![password.clone()],
+                DockerCredentials::Composite { auth } => {
+                    let mut values = vec![auth.clone()];
+                    values.extend(credentials.unpack().ok().map(|(_, password)| password));
+                    values
+                }
+            })
+            .collect()
+    }
+
     /// Returns a [`RegistryAuth`] for a given image registry.
     /// If a registry is not mentioned in the auth section of the docker config file,
     /// the authentication method will be "anonymous" (i.e. unauthenticated), which
@@ -167,6 +195,35 @@ mod tests {
         DockerConfig::from_str(src).expect("no errors");
     }
 
+    #[test]
+    fn merge() {
+        let mut config = DockerConfig::from_str(
+            r#"{"auths": {"us-docker.pkg.dev": {"username": "foo", "password": "hunter12"}}}"#,
+        )
+        .expect("no errors");
+        config.merge(
+            DockerConfig::from_str(
+                r#"{"auths": {
+                    "us-docker.pkg.dev": {"username": "bar", "password": "hunter13"},
+                    "mirror.example.com": {"auth": "Zm9vOmh1bnRlcjEy"}
+                }}"#,
+            )
+            .expect("no errors"),
+        );
+
+        let config =
+            DockerConfig::from_slice(&config.to_vec().expect("no errors")).expect("round trips");
+        let auth = config.get_auth("us-docker.pkg.dev").expect("no errors");
+        assert_matches!(auth, RegistryAuth::Basic(username, password) if username == "foo" && password == "hunter12");
+        let auth = config.get_auth("mirror.example.com").expect("no errors");
+        assert_matches!(auth, RegistryAuth::Basic(username, password) if username == "foo" && password == "hunter12");
+
+        assert_eq!(
+            config.secret_values(),
+            ["Zm9vOmh1bnRlcjEy", "hunter12", "hunter12"]
+        );
+    }
+
     #[test]
     fn bad_json() {
         let src = r#"
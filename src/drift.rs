@@ -0,0 +1,315 @@
+use std::collections::BTreeSet;
+
+/// A live object whose state differs from what the package would apply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftedObject {
+    /// The object as named by `kubectl diff`, i.e. `<group>.<version>.<Kind>.<namespace>.<name>`.
+    pub object: String,
+    /// The fields that changed.
+    pub fields: Vec<String>,
+}
+
+impl std::fmt::Display for DriftedObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.object, self.fields.join(", "))
+    }
+}
+
+/// Returns the objects that changed according to the unified diff output of `kubectl diff`.
+///
+/// The diff is expected to show the objects in full, so that the fields are reported by their
+/// path, e.g. `spec.template.spec.containers[0].image`; the fields within a changed one are not
+/// reported on their own.
+///
+/// Changes that are expected to show up on every diff (the generation bump and the applyset
+/// labels that `kubectl apply --applyset` adds to the live objects) are not considered drift.
+pub fn parse_diff(output: &str) -> Vec<DriftedObject> {
+    let mut drifted = vec![];
+    let mut current: Option<(String, BTreeSet<String>)> = None;
+    // The live and the merged objects, which are read from the removed and the added lines
+    // respectively, in addition to the common ones.
+    let mut live = YamlPath::default();
+    let mut merged = YamlPath::default();
+
+    let mut flush = |current: Option<(String, BTreeSet<String>)>| {
+        if let Some((object, fields)) = current {
+            if !fields.is_empty() {
+                drifted.push(DriftedObject {
+                    object,
+                    fields: fields.into_iter().collect(),
+                });
+            }
+        }
+    };
+
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("diff ") {
+            flush(current.take());
+            let object = header
+                .split_whitespace()
+                .last()
+                .and_then(|path| path.rsplit('/').next())
+                .unwrap_or_default();
+            current = Some((object.to_string(), BTreeSet::new()));
+            live = YamlPath::default();
+            merged = YamlPath::default();
+            continue;
+        }
+        if line.starts_with("+++") || line.starts_with("---") || line.starts_with("@@") {
+            continue;
+        }
+        let Some((_, fields)) = current.as_mut() else {
+            continue;
+        };
+        let (side, content) = match line.split_at_checked(1) {
+            Some(("-", content)) => (&mut live, content),
+            Some(("+", content)) => (&mut merged, content),
+            Some((" ", content)) => {
+                live.push_line(content, false);
+                merged.push_line(content, false);
+                continue;
+            }
+            _ => continue,
+        };
+        let Some(field) = side.push_line(content, true) else {
+            continue;
+        };
+        if content.contains("applyset.kubernetes.io") || field == "metadata.generation" {
+            continue;
+        }
+        fields.insert(field);
+    }
+    flush(current);
+
+    drifted
+}
+
+/// A field of the YAML rendering of an object, as seen by [`YamlPath`].
+#[derive(Debug)]
+struct Segment {
+    indent: usize,
+    /// The key of a mapping entry, or the index of a sequence item.
+    key: Result<String, usize>,
+    changed: bool,
+}
+
+/// Tracks the path of the lines of the YAML rendering of an object, as output by kubectl,
+/// i.e. with block mappings and sequences only.
+#[derive(Debug, Default)]
+struct YamlPath {
+    segments: Vec<Segment>,
+    /// Indentation of the key of a block scalar (`|` or `>`), whose lines are its value.
+    block_scalar: Option<usize>,
+}
+
+impl YamlPath {
+    /// Moves to the given line; if it changed, returns the path of its field unless the field
+    /// is within another changed one.
+    fn push_line(&mut self, line: &str, changed: bool) -> Option<String> {
+        let rest = line.trim_start();
+        let indent = line.len() - rest.len();
+        if rest.is_empty() {
+            return None;
+        }
+        match self.block_scalar {
+            Some(key_indent) if indent > key_indent => {
+                return self.changed_path(self.segments.len(), changed)
+            }
+            _ => self.block_scalar = None,
+        }
+
+        let item = rest
+            .strip_prefix('-')
+            .filter(|item| item.is_empty() || item.starts_with(' '));
+        let Some(item) = item else {
+            while self.segments.last().is_some_and(|s| s.indent >= indent) {
+                self.segments.pop();
+            }
+            let base = self.segments.len();
+            self.push_entry(indent, rest);
+            return self.changed_path(base, changed);
+        };
+
+        // Items are indented like the key of their sequence, or more.
+        while self.segments.last().is_some_and(|s| s.indent > indent) {
+            self.segments.pop();
+        }
+        let index = match self.segments.last() {
+            Some(Segment {
+                indent: i,
+                key: Err(index),
+                ..
+            }) if *i == indent => {
+                let index = index + 1;
+                self.segments.pop();
+                index
+            }
+            _ => 0,
+        };
+        let base = self.segments.len();
+        self.segments.push(Segment {
+            indent,
+            key: Err(index),
+            changed: false,
+        });
+        let entry = item.trim_start();
+        if is_mapping_entry(entry) {
+            self.push_entry(indent + (rest.len() - entry.len()), entry);
+        }
+        self.changed_path(base, changed)
+    }
+
+    fn push_entry(&mut self, indent: usize, entry: &str) {
+        let (key, value) = entry
+            .split_once(": ")
+            .unwrap_or((entry.trim_end_matches(':'), ""));
+        self.segments.push(Segment {
+            indent,
+            key: Ok(key.to_string()),
+            changed: false,
+        });
+        if value.starts_with(['|', '>']) {
+            self.block_scalar = Some(indent);
+        }
+    }
+
+    /// Returns the path of the current field if it changed, unless it's within a field that
+    /// changed too, i.e. one before `base`, the first segment of the current line.
+    fn changed_path(&mut self, base: usize, changed: bool) -> Option<String> {
+        if !changed || self.segments[..base].iter().any(|s| s.changed) {
+            return None;
+        }
+        // The item of a `- key: value` line may have only that key changed.
+        if let Some(segment) = self.segments.last_mut() {
+            segment.changed = true;
+        }
+        let mut path = String::new();
+        for segment in &self.segments {
+            match &segment.key {
+                Ok(key) if path.is_empty() => path.push_str(key),
+                Ok(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                Err(index) => path.push_str(&format!("[{index}]")),
+            }
+        }
+        Some(path)
+    }
+}
+
+/// Tells a `key: value` (or `key:`) entry from a scalar.
+fn is_mapping_entry(entry: &str) -> bool {
+    !entry.starts_with(['"', '\'']) && (entry.contains(": ") || entry.ends_with(':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_kubectl_diff() {
+        let output = r#"diff -N -U 1000000 /tmp/LIVE-1234/apps.v1.Deployment.test.web /tmp/MERGED-5678/apps.v1.Deployment.test.web
+--- /tmp/LIVE-1234/apps.v1.Deployment.test.web	2024-01-01 00:00:00.000000000 +0000
++++ /tmp/MERGED-5678/apps.v1.Deployment.test.web	2024-01-01 00:00:00.000000000 +0000
+@@ -1,28 +1,32 @@
+ apiVersion: apps/v1
+ kind: Deployment
+ metadata:
+-  generation: 4
++  generation: 5
+   labels:
+     app: web
+   name: web
+   namespace: test
+ spec:
+-  replicas: 5
++  replicas: 2
+   template:
+     spec:
+       containers:
+       - args:
+         - --port=8080
+-        - --debug
+         image: web:v1
+         name: web
+-      - image: sidecar:debug
++      - image: sidecar:v1
+         name: sidecar
++      - image: proxy:v1
++        name: proxy
++        ports:
++        - containerPort: 80
+       volumes:
+       - configMap:
+           name: web
+         name: config
+diff -N -U 1000000 /tmp/LIVE-1234/v1.ConfigMap.test.settings /tmp/MERGED-5678/v1.ConfigMap.test.settings
+--- /tmp/LIVE-1234/v1.ConfigMap.test.settings	2024-01-01 00:00:00.000000000 +0000
++++ /tmp/MERGED-5678/v1.ConfigMap.test.settings	2024-01-01 00:00:00.000000000 +0000
+@@ -1,12 +1,11 @@
+ apiVersion: v1
+ data:
+   settings.yaml: |
+-    level: debug
++    level: info
+     format: json
+ kind: ConfigMap
+ metadata:
+   labels:
+-    applyset.kubernetes.io/part-of: applyset-abc-v1
+     app: web
+   name: settings
+   namespace: test
+diff -N -U 1000000 /tmp/LIVE-1234/v1.Service.test.other /tmp/MERGED-5678/v1.Service.test.other
+--- /tmp/LIVE-1234/v1.Service.test.other	2024-01-01 00:00:00.000000000 +0000
++++ /tmp/MERGED-5678/v1.Service.test.other	2024-01-01 00:00:00.000000000 +0000
+@@ -1,4 +1,4 @@
+ metadata:
+-  applyset.kubernetes.io/part-of: applyset-abc-v1
++  applyset.kubernetes.io/part-of: applyset-def-v1
+   name: other
+"#;
+
+        assert_eq!(
+            parse_diff(output),
+            vec![
+                DriftedObject {
+                    object: "apps.v1.Deployment.test.web".to_string(),
+                    fields: vec![
+                        "spec.replicas".to_string(),
+                        "spec.template.spec.containers[0].args[1]".to_string(),
+                        "spec.template.spec.containers[1].image".to_string(),
+                        "spec.template.spec.containers[2].image".to_string(),
+                        "spec.template.spec.containers[2].name".to_string(),
+                        "spec.template.spec.containers[2].ports".to_string(),
+                    ],
+                },
+                DriftedObject {
+                    object: "v1.ConfigMap.test.settings".to_string(),
+                    fields: vec!["data.settings.yaml".to_string()],
+                },
+            ]
+        );
+        assert!(parse_diff("").is_empty());
+    }
+
+    #[test]
+    fn new_object() {
+        let output = r#"diff -N -U 1000000 /tmp/LIVE-1234/v1.ConfigMap.test.new /tmp/MERGED-5678/v1.ConfigMap.test.new
+--- /tmp/LIVE-1234/v1.ConfigMap.test.new	2024-01-01 00:00:00.000000000 +0000
++++ /tmp/MERGED-5678/v1.ConfigMap.test.new	2024-01-01 00:00:00.000000000 +0000
+@@ -0,0 +1,6 @@
++apiVersion: v1
++data:
++  key: value
++kind: ConfigMap
++metadata:
++  name: new
+"#;
+        assert_eq!(
+            parse_diff(output)[0].fields,
+            ["apiVersion", "data", "kind", "metadata"]
+        );
+    }
+}
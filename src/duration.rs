@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use crate::{Error, Result};
+
+/// Lower bound of the intervals at which jobs are run periodically, lest a typo like `0s` spawn
+/// them in a tight loop.
+pub const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Parses a duration in the format used by Kubernetes and Go, e.g. `90s`, `10m` or `1h30m`.
+pub fn parse(s: &str) -> Result<Duration> {
+    let invalid = || Error::InvalidDuration(s.to_string());
+
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let value: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(1),
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+
+        let value = u32::try_from(value).map_err(|_| invalid())?;
+        total += unit.checked_mul(value).ok_or_else(invalid)?;
+    }
+
+    Ok(total)
+}
+
+/// Parses the interval at which a job is run periodically, which must be at least [`MIN_INTERVAL`].
+pub fn parse_interval(s: &str) -> Result<Duration> {
+    let interval = parse(s)?;
+    if interval < MIN_INTERVAL {
+        return Err(Error::IntervalTooShort(s.to_string()));
+    }
+    Ok(interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse("1m500ms").unwrap(), Duration::from_millis(60_500));
+
+        for bad in ["", "10", "m", "1d", "1h-1m", "1.5h"] {
+            assert!(parse(bad).is_err(), "{bad:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn parse_intervals() {
+        assert_eq!(parse_interval("1m").unwrap(), MIN_INTERVAL);
+        assert_eq!(parse_interval("1h").unwrap(), Duration::from_secs(3600));
+
+        for short in ["0s", "0m", "59s", "500ms"] {
+            assert!(
+                matches!(parse_interval(short), Err(Error::IntervalTooShort(_))),
+                "{short:?} should be too short"
+            );
+        }
+        assert!(matches!(
+            parse_interval("soon"),
+            Err(Error::InvalidDuration(_))
+        ));
+    }
+}
@@ -0,0 +1,657 @@
+use std::{env, io::Write, path::Path};
+
+use home::home_dir;
+use kube::ResourceExt;
+use oci_distribution::Reference;
+
+use crate::{
+    manifests, metadata,
+    oci::PackageConfig,
+    render,
+    resources::AppInstance,
+    scripting::{self, Script},
+    Error, Result,
+};
+
+/// Media type of the config of Helm charts pushed to OCI registries.
+pub const HELM_CONFIG_MEDIA_TYPE: &str = "application/vnd.cncf.helm.config.v1+json";
+
+pub const DEFAULT_HELM_IMAGE: &str = "docker.io/alpine/helm:3.17.3";
+
+/// Images the render step runs, per engine.
+#[derive(Clone, Debug)]
+pub struct RenderImages {
+    /// Tagged with the kubecfg version each package was built with.
+    pub kubecfg: String,
+    pub helm: String,
+    /// Fetches the manifests of plain manifest bundles.
+    pub kubit: String,
+    /// Builds kustomizations with `kubectl kustomize`.
+    pub kubectl: String,
+}
+
+/// Directory, next to the generated kustomization, where the package is unpacked.
+pub const KUSTOMIZE_PACKAGE_DIR: &str = "package";
+
+/// Engine that renders the manifests of a package, chosen from the package config.
+///
+/// The manifests are then applied the same way whatever the engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum RenderEngine {
+    /// Jsonnet packages built with `kubecfg pack`, rendered with `kubecfg show`.
+    Kubecfg,
+    /// Helm charts, rendered with `helm template` using `spec.package.spec` as values.
+    Helm,
+    /// Bundles of plain manifests, e.g. pushed with `oras` or `flux push artifact`, with their
+    /// `${name}` variables substituted from `spec.package.spec`.
+    Manifests,
+    /// Bundles of a kustomization root, built with `kubectl kustomize` through a generated
+    /// kustomization setting the namespace, images, patches and replicas of `spec.package.spec`.
+    Kustomize,
+}
+
+impl RenderEngine {
+    /// Picks the engine of a package from the `engine` declared under `kubit.kubecfg.dev/v1alpha1`
+    /// in its metadata, or else from the media type of its config: Helm charts and artifacts
+    /// pushed with `oras` or `flux` are recognized as such and anything else is a kubecfg
+    /// package.
+    pub fn for_package(package_config: &PackageConfig) -> Result<Self> {
+        match package_config.engine() {
+            Some(engine) => <Self as clap::ValueEnum>::from_str(engine, true)
+                .map_err(|_| Error::UnsupportedRenderEngine(engine.to_string())),
+            None if package_config.media_type() == HELM_CONFIG_MEDIA_TYPE => Ok(RenderEngine::Helm),
+            None if package_config.media_type() == manifests::FLUX_CONFIG_MEDIA_TYPE
+                || manifests::ORAS_CONFIG_MEDIA_TYPES.contains(&package_config.media_type()) =>
+            {
+                Ok(RenderEngine::Manifests)
+            }
+            None => Ok(RenderEngine::Kubecfg),
+        }
+    }
+
+    /// Picks the engine of a package from the CLI, without failing when the package config
+    /// cannot be fetched: the render step reports that error more helpfully.
+    pub async fn for_local_package(app_instance: &AppInstance, skip_auth: bool) -> Self {
+        if app_instance.spec.package.image.starts_with("file://") {
+            return RenderEngine::Kubecfg;
+        }
+        metadata::fetch_package_config_local_auth(app_instance, skip_auth)
+            .await
+            .ok()
+            .and_then(|package_config| Self::for_package(&package_config).ok())
+            .unwrap_or(RenderEngine::Kubecfg)
+    }
+
+    /// Image of the container rendering the package in the apply job.
+    pub fn image(&self, package_config: &PackageConfig, images: &RenderImages) -> Result<String> {
+        match self {
+            RenderEngine::Kubecfg => Ok(package_config.versioned_kubecfg_image(&images.kubecfg)?),
+            RenderEngine::Helm => Ok(images.helm.clone()),
+            RenderEngine::Manifests => Ok(images.kubit.clone()),
+            RenderEngine::Kustomize => Ok(images.kubectl.clone()),
+        }
+    }
+
+    /// Version of kubecfg the package was built with, recorded in the status.
+    /// Packages of the other engines aren't built with kubecfg.
+    pub fn kubecfg_version(&self, package_config: &PackageConfig) -> Result<Option<String>> {
+        match self {
+            RenderEngine::Kubecfg => Ok(Some(package_config.kubecfg_package_metadata()?.version)),
+            RenderEngine::Helm | RenderEngine::Manifests | RenderEngine::Kustomize => Ok(None),
+        }
+    }
+
+    /// Writes the overlay through which the render step gets the instance.
+    pub fn write_overlay<W: Write>(&self, app_instance: &AppInstance, w: W) -> Result<()> {
+        match self {
+            // Passed to kubecfg as the `appInstance_` external code.
+            RenderEngine::Kubecfg => {
+                serde_json::to_writer_pretty(w, app_instance).map_err(Error::RenderOverlay)
+            }
+            // The values of the chart or of the variables; JSON is valid YAML.
+            RenderEngine::Helm | RenderEngine::Manifests => {
+                serde_json::to_writer_pretty(w, &app_instance.spec.package.spec)
+                    .map_err(Error::RenderOverlay)
+            }
+            RenderEngine::Kustomize => {
+                serde_json::to_writer_pretty(w, &kustomization(app_instance)?)
+                    .map_err(Error::RenderOverlay)
+            }
+        }
+    }
+
+    /// Command line fetching the package before it's rendered, for the engines whose tool
+    /// cannot pull it by itself.
+    ///
+    /// The kustomization root is unpacked next to the generated kustomization, which is copied
+    /// from the overlay.
+    pub fn fetch_commandline(
+        &self,
+        app_instance: &AppInstance,
+        overlay_file: &str,
+        skip_auth: bool,
+    ) -> Option<Vec<String>> {
+        match self {
+            RenderEngine::Kubecfg | RenderEngine::Helm | RenderEngine::Manifests => None,
+            RenderEngine::Kustomize => {
+                let mut cli: Vec<String> = [
+                    "kubit",
+                    "helper",
+                    "fetch-kustomization",
+                    "--overlay",
+                    overlay_file,
+                    "--output",
+                    &kustomize_dir(overlay_file),
+                ]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+                if skip_auth {
+                    cli.push("--skip-auth".to_string());
+                }
+                cli.push(app_instance.spec.package.image.clone());
+                Some(cli)
+            }
+        }
+    }
+
+    /// Returns the overlay to render the AppInstance read from `manifest_file` with.
+    ///
+    /// kubecfg reads the manifest itself; the overlays of the other engines are written to
+    /// temporary files that are kept, since the rendering script may run after kubit exits.
+    pub fn local_overlay(&self, app_instance: &AppInstance, manifest_file: &str) -> Result<String> {
+        match self {
+            RenderEngine::Kubecfg => Ok(manifest_file.to_string()),
+            RenderEngine::Helm | RenderEngine::Manifests | RenderEngine::Kustomize => {
+                let (mut file, path) = tempfile::Builder::new()
+                    .suffix(".json")
+                    .tempfile()?
+                    .keep()?;
+                self.write_overlay(app_instance, &mut file)?;
+                Ok(path.to_string_lossy().to_string())
+            }
+        }
+    }
+
+    /// Command line rendering the package into `output_dir`, or to stdout.
+    pub async fn emit_commandline(
+        &self,
+        app_instance: &AppInstance,
+        overlay_file: &str,
+        output_dir: Option<&str>,
+        docker: bool,
+        skip_auth: bool,
+        images: &RenderImages,
+    ) -> Vec<String> {
+        match self {
+            RenderEngine::Kubecfg => {
+                render::emit_commandline(
+                    app_instance,
+                    overlay_file,
+                    output_dir,
+                    docker,
+                    skip_auth,
+                    images.kubecfg.clone(),
+                )
+                .await
+            }
+            RenderEngine::Helm => emit_helm_commandline(
+                app_instance,
+                overlay_file,
+                output_dir,
+                docker,
+                skip_auth,
+                &images.helm,
+            ),
+            RenderEngine::Manifests => {
+                emit_manifests_commandline(app_instance, overlay_file, output_dir, skip_auth)
+            }
+            RenderEngine::Kustomize => {
+                emit_kustomize_commandline(overlay_file, output_dir, docker, &images.kubectl)
+            }
+        }
+    }
+
+    /// Shell script fetching the package if needed, and rendering it into `output_dir`,
+    /// or to stdout.
+    pub async fn script(
+        &self,
+        app_instance: &AppInstance,
+        overlay_file: &str,
+        output_dir: Option<&str>,
+        docker: bool,
+        skip_auth: bool,
+        images: &RenderImages,
+    ) -> Result<Script> {
+        let tokens = self
+            .emit_commandline(
+                app_instance,
+                overlay_file,
+                output_dir,
+                docker,
+                skip_auth,
+                images,
+            )
+            .await;
+        let script = Script::from_vec(tokens);
+        Ok(
+            match self.fetch_commandline(app_instance, overlay_file, skip_auth) {
+                Some(fetch) => (Script::from_vec(fetch) + script).subshell(),
+                None => script,
+            },
+        )
+    }
+}
+
+impl std::fmt::Display for RenderEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let engine = match self {
+            RenderEngine::Kubecfg => "kubecfg",
+            RenderEngine::Helm => "helm",
+            RenderEngine::Manifests => "manifests",
+            RenderEngine::Kustomize => "kustomize",
+        };
+        write!(f, "{engine}")
+    }
+}
+
+/// Command line running `helm template` on the chart of the instance.
+///
+/// In the apply job the output is redirected into `output_dir`, where the apply step expects
+/// the manifests, so the command is wrapped in a shell, which also expands `DOCKER_CONFIG`.
+fn emit_helm_commandline(
+    app_instance: &AppInstance,
+    overlay_file: &str,
+    output_dir: Option<&str>,
+    docker: bool,
+    skip_auth: bool,
+    helm_image: &str,
+) -> Vec<String> {
+    let image = &app_instance.spec.package.image;
+    // helm pulls a digest by appending it to the chart reference, and a tag with --version.
+    let (chart, version) = match image.parse::<Reference>() {
+        Ok(reference) => {
+            let repository = format!("oci://{}/{}", reference.registry(), reference.repository());
+            match reference.digest() {
+                Some(digest) => (format!("{repository}@{digest}"), None),
+                None => (repository, reference.tag().map(str::to_string)),
+            }
+        }
+        Err(_) => (format!("oci://{image}"), None),
+    };
+
+    let mut cli: Vec<String> = vec![];
+    let mut values = overlay_file.to_string();
+    let mut registry_config = output_dir.map(|_| "${DOCKER_CONFIG}/config.json".to_string());
+
+    if docker {
+        let overlay_path = std::fs::canonicalize(overlay_file).unwrap();
+        values = format!(
+            "/overlay/{}",
+            overlay_path.file_name().unwrap().to_string_lossy()
+        );
+        cli.extend(
+            [
+                "docker",
+                "run",
+                "--rm",
+                "--network",
+                "host",
+                "-v",
+                &format!("{}:{values}", overlay_path.display()),
+            ]
+            .iter()
+            .map(|s| s.to_string()),
+        );
+        if !skip_auth {
+            let user_home = home_dir().expect("unable to retrieve home directory");
+            let docker_config =
+                env::var("DOCKER_CONFIG").unwrap_or(format!("{}/.docker", user_home.display()));
+            cli.extend(["-v".to_string(), format!("{docker_config}:/.docker")]);
+            registry_config = Some("/.docker/config.json".to_string());
+        }
+        // The entrypoint of the image is helm itself.
+        cli.push(helm_image.to_string());
+    } else {
+        cli.push("helm".to_string());
+    }
+
+    cli.extend(
+        [
+            "template",
+            &app_instance.name_any(),
+            &chart,
+            "--namespace",
+            &app_instance.namespace().unwrap_or_default(),
+            "--include-crds",
+            "--values",
+            &values,
+        ]
+        .iter()
+        .map(|s| s.to_string()),
+    );
+    if let Some(version) = version {
+        cli.extend(["--version".to_string(), version]);
+    }
+    if let Some(registry_config) = registry_config {
+        cli.extend(["--registry-config".to_string(), registry_config]);
+    }
+
+    match output_dir {
+        Some(output_dir) => vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            format!(
+                "{} > {}",
+                cli.iter()
+                    .map(scripting::quoted)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                scripting::quoted(&format!("{output_dir}/manifests.yaml"))
+            ),
+        ],
+        None => cli,
+    }
+}
+
+/// Command line fetching the manifests of the bundle with kubit itself, which needs no
+/// container locally either.
+fn emit_manifests_commandline(
+    app_instance: &AppInstance,
+    overlay_file: &str,
+    output_dir: Option<&str>,
+    skip_auth: bool,
+) -> Vec<String> {
+    let mut cli: Vec<String> = [
+        "kubit",
+        "helper",
+        "fetch-manifests",
+        "--values",
+        overlay_file,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    if let Some(output_dir) = output_dir {
+        cli.extend(["--output".to_string(), output_dir.to_string()]);
+    }
+    if skip_auth {
+        cli.push("--skip-auth".to_string());
+    }
+    cli.push(app_instance.spec.package.image.clone());
+    cli
+}
+
+/// Returns the kustomization through which the package is built for the instance.
+///
+/// The namespace defaults to the one of the instance, and `images`, `patches` and `replicas`
+/// are passed as is, so they follow the kustomization syntax.
+fn kustomization(app_instance: &AppInstance) -> Result<serde_json::Value> {
+    let spec =
+        serde_json::to_value(&app_instance.spec.package.spec).map_err(Error::RenderOverlay)?;
+    let mut kustomization = serde_json::json!({
+        "apiVersion": "kustomize.config.k8s.io/v1beta1",
+        "kind": "Kustomization",
+        "resources": [KUSTOMIZE_PACKAGE_DIR],
+        "namespace": spec.get("namespace").cloned().unwrap_or(app_instance.namespace().into()),
+    });
+    for field in ["images", "patches", "replicas"] {
+        if let Some(value) = spec.get(field) {
+            kustomization[field] = value.clone();
+        }
+    }
+    Ok(kustomization)
+}
+
+/// Directory where the kustomization is built, next to the overlay it is generated from.
+fn kustomize_dir(overlay_file: &str) -> String {
+    Path::new(overlay_file)
+        .with_extension("d")
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Command line running `kubectl kustomize` on the unpacked package.
+fn emit_kustomize_commandline(
+    overlay_file: &str,
+    output_dir: Option<&str>,
+    docker: bool,
+    kubectl_image: &str,
+) -> Vec<String> {
+    let dir = kustomize_dir(overlay_file);
+    match output_dir {
+        Some(output_dir) => vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            format!(
+                "kubectl kustomize {} > {}",
+                scripting::quoted(&dir),
+                scripting::quoted(&format!("{output_dir}/manifests.yaml"))
+            ),
+        ],
+        // The entrypoint of the image is kubectl itself.
+        None if docker => [
+            "docker",
+            "run",
+            "--rm",
+            "-v",
+            &format!("{dir}:/kustomization"),
+            kubectl_image,
+            "kustomize",
+            "/kustomization",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+        None => vec!["kubectl".to_string(), "kustomize".to_string(), dir],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_config(media_type: &str, metadata: serde_json::Value) -> PackageConfig {
+        serde_json::from_value::<PackageConfig>(serde_json::json!({
+            "entrypoint": "main.jsonnet",
+            "metadata": metadata,
+        }))
+        .unwrap()
+        .with_media_type(media_type)
+    }
+
+    #[test]
+    fn engine_from_metadata() {
+        let legacy = package_config("application/json", serde_json::json!({}));
+        assert_eq!(
+            RenderEngine::for_package(&legacy).unwrap(),
+            RenderEngine::Kubecfg
+        );
+
+        let declared = package_config(
+            "application/json",
+            serde_json::json!({
+                "kubit.kubecfg.dev/v1alpha1": { "engine": "kubecfg" },
+            }),
+        );
+        assert_eq!(
+            RenderEngine::for_package(&declared).unwrap(),
+            RenderEngine::Kubecfg
+        );
+
+        let unknown = package_config(
+            "application/json",
+            serde_json::json!({
+                "kubit.kubecfg.dev/v1alpha1": { "engine": "ksonnet" },
+            }),
+        );
+        assert!(matches!(
+            RenderEngine::for_package(&unknown),
+            Err(Error::UnsupportedRenderEngine(_))
+        ));
+
+        let chart = package_config(HELM_CONFIG_MEDIA_TYPE, serde_json::json!({}));
+        assert_eq!(
+            RenderEngine::for_package(&chart).unwrap(),
+            RenderEngine::Helm
+        );
+
+        let kustomization = package_config(
+            manifests::ORAS_CONFIG_MEDIA_TYPES[0],
+            serde_json::json!({
+                "kubit.kubecfg.dev/v1alpha1": { "engine": "kustomize" },
+            }),
+        );
+        assert_eq!(
+            RenderEngine::for_package(&kustomization).unwrap(),
+            RenderEngine::Kustomize
+        );
+
+        let bundle = package_config(manifests::FLUX_CONFIG_MEDIA_TYPE, serde_json::json!({}));
+        assert_eq!(
+            RenderEngine::for_package(&bundle).unwrap(),
+            RenderEngine::Manifests
+        );
+    }
+
+    #[test]
+    fn generated_kustomization() {
+        let app_instance: AppInstance = serde_yaml::from_str(
+            r#"
+apiVersion: kubecfg.dev/v1alpha1
+kind: AppInstance
+metadata:
+  name: demo
+  namespace: apps
+spec:
+  package:
+    image: ghcr.io/example/kustomize/demo:v1
+    apiVersion: v1
+    spec:
+      images:
+        - name: demo
+          newTag: v1.2.3
+      replicas:
+        - name: demo
+          count: 2
+      labels:
+        ignored: "true"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            kustomization(&app_instance).unwrap(),
+            serde_json::json!({
+                "apiVersion": "kustomize.config.k8s.io/v1beta1",
+                "kind": "Kustomization",
+                "resources": ["package"],
+                "namespace": "apps",
+                "images": [{ "name": "demo", "newTag": "v1.2.3" }],
+                "replicas": [{ "name": "demo", "count": 2 }],
+            })
+        );
+        assert_eq!(
+            RenderEngine::Kustomize.fetch_commandline(
+                &app_instance,
+                "/overlay/appinstance.json",
+                false
+            ),
+            Some(
+                [
+                    "kubit",
+                    "helper",
+                    "fetch-kustomization",
+                    "--overlay",
+                    "/overlay/appinstance.json",
+                    "--output",
+                    "/overlay/appinstance.d",
+                    "ghcr.io/example/kustomize/demo:v1",
+                ]
+                .map(String::from)
+                .to_vec()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn helm_commandline() {
+        let mut app_instance: AppInstance = serde_yaml::from_str(
+            r#"
+apiVersion: kubecfg.dev/v1alpha1
+kind: AppInstance
+metadata:
+  name: demo
+  namespace: apps
+spec:
+  package:
+    image: ghcr.io/example/charts/demo@sha256:0123
+    apiVersion: v2
+    spec: {}
+"#,
+        )
+        .unwrap();
+        let images = RenderImages {
+            kubecfg: "kubecfg".to_string(),
+            helm: DEFAULT_HELM_IMAGE.to_string(),
+            kubit: "kubit".to_string(),
+            kubectl: "kubectl".to_string(),
+        };
+
+        let cli = RenderEngine::Helm
+            .emit_commandline(
+                &app_instance,
+                "/overlay/values.json",
+                Some("/manifests"),
+                false,
+                false,
+                &images,
+            )
+            .await;
+        assert_eq!(
+            cli,
+            [
+                "/bin/sh",
+                "-c",
+                "helm template demo oci://ghcr.io/example/charts/demo@sha256:0123 --namespace apps \
+                 --include-crds --values /overlay/values.json \
+                 --registry-config \"${DOCKER_CONFIG}/config.json\" > /manifests/manifests.yaml",
+            ]
+        );
+
+        // Unparseable references are passed to helm as is, but never interpreted by the shell.
+        app_instance.spec.package.image = "demo;reboot".to_string();
+        let cli = RenderEngine::Helm
+            .emit_commandline(
+                &app_instance,
+                "/overlay/values.json",
+                Some("/manifests"),
+                false,
+                false,
+                &images,
+            )
+            .await;
+        assert!(cli[2].starts_with("helm template demo 'oci://demo;reboot' --namespace apps"));
+
+        app_instance.spec.package.image = "ghcr.io/example/charts/demo:1.2.3".to_string();
+        let cli = RenderEngine::Helm
+            .emit_commandline(&app_instance, "values.json", None, false, false, &images)
+            .await;
+        assert_eq!(
+            cli,
+            [
+                "helm",
+                "template",
+                "demo",
+                "oci://ghcr.io/example/charts/demo",
+                "--namespace",
+                "apps",
+                "--include-crds",
+                "--values",
+                "values.json",
+                "--version",
+                "1.2.3",
+            ]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use kube::ResourceExt;
+use serde_json::Value;
+
+use crate::inventory::AppliedObject;
+
+/// Aggregated health of the resources installed by an AppInstance.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Health {
+    /// All the resources have rolled out and report being ready.
+    Healthy,
+    /// Some resources are still rolling out; holds one message per resource.
+    Progressing(Vec<String>),
+    /// Some resources have failed; holds one message per resource.
+    Degraded(Vec<String>),
+}
+
+/// Health of a single resource.
+#[derive(Debug, Clone, PartialEq)]
+enum ObjectHealth {
+    Healthy,
+    Progressing(String),
+    Degraded(String),
+}
+
+/// Assesses the health of the given applied resources.
+///
+/// A single degraded resource makes the whole set degraded, otherwise the set is
+/// progressing until every resource is healthy.
+pub fn assess(objects: &[AppliedObject]) -> Health {
+    let mut progressing = vec![];
+    let mut degraded = vec![];
+
+    for applied in objects {
+        let object = &applied.object;
+        let id = match object.namespace() {
+            Some(ns) => format!("{} {ns}/{}", applied.resource.kind, object.name_any()),
+            None => format!("{} {}", applied.resource.kind, object.name_any()),
+        };
+        let generation = object.metadata.generation;
+        let data = &object.data;
+
+        let health = match (
+            applied.resource.group.as_str(),
+            applied.resource.kind.as_str(),
+        ) {
+            ("apps", "Deployment") => deployment_health(generation, data),
+            ("apps", "StatefulSet") => stateful_set_health(generation, data),
+            ("apps", "DaemonSet") => daemon_set_health(generation, data),
+            ("batch", "Job") => job_health(data),
+            ("", "PersistentVolumeClaim") => pvc_health(data),
+            _ => generic_health(generation, data),
+        };
+
+        match health {
+            ObjectHealth::Healthy => {}
+            ObjectHealth::Progressing(msg) => progressing.push(format!("{id}: {msg}")),
+            ObjectHealth::Degraded(msg) => degraded.push(format!("{id}: {msg}")),
+        }
+    }
+
+    if !degraded.is_empty() {
+        Health::Degraded(degraded)
+    } else if !progressing.is_empty() {
+        Health::Progressing(progressing)
+    } else {
+        Health::Healthy
+    }
+}
+
+fn int(data: &Value, pointer: &str) -> Option<i64> {
+    data.pointer(pointer).and_then(Value::as_i64)
+}
+
+fn str_at<'a>(data: &'a Value, pointer: &str) -> Option<&'a str> {
+    data.pointer(pointer).and_then(Value::as_str)
+}
+
+fn condition<'a>(data: &'a Value, type_: &str) -> Option<&'a Value> {
+    data.pointer("/status/conditions")?
+        .as_array()?
+        .iter()
+        .find(|c| c.get("type").and_then(Value::as_str) == Some(type_))
+}
+
+fn condition_status<'a>(data: &'a Value, type_: &str) -> Option<&'a str> {
+    condition(data, type_)?.get("status")?.as_str()
+}
+
+fn condition_message(data: &Value, type_: &str) -> String {
+    condition(data, type_)
+        .and_then(|c| c.get("message").or_else(|| c.get("reason")))
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Returns a progressing health if the controller of the resource hasn't yet observed its latest spec.
+fn observed(generation: Option<i64>, data: &Value) -> Option<ObjectHealth> {
+    match (generation, int(data, "/status/observedGeneration")) {
+        (Some(generation), Some(observed)) if observed < generation => Some(
+            ObjectHealth::Progressing("waiting for the spec update to be observed".to_string()),
+        ),
+        _ => None,
+    }
+}
+
+fn deployment_health(generation: Option<i64>, data: &Value) -> ObjectHealth {
+    if let Some(health) = observed(generation, data) {
+        return health;
+    }
+    if condition(data, "Progressing")
+        .and_then(|c| c.get("reason"))
+        .and_then(Value::as_str)
+        == Some("ProgressDeadlineExceeded")
+    {
+        return ObjectHealth::Degraded(condition_message(data, "Progressing"));
+    }
+
+    let replicas = int(data, "/spec/replicas").unwrap_or(1);
+    let updated = int(data, "/status/updatedReplicas").unwrap_or(0);
+    let available = int(data, "/status/availableReplicas").unwrap_or(0);
+    let total = int(data, "/status/replicas").unwrap_or(0);
+
+    if updated < replicas {
+        ObjectHealth::Progressing(format!("{updated}/{replicas} replicas updated"))
+    } else if total > updated {
+        ObjectHealth::Progressing(format!(
+            "{} old replicas pending termination",
+            total - updated
+        ))
+    } else if available < replicas {
+        ObjectHealth::Progressing(format!("{available}/{replicas} replicas available"))
+    } else {
+        ObjectHealth::Healthy
+    }
+}
+
+fn stateful_set_health(generation: Option<i64>, data: &Value) -> ObjectHealth {
+    if let Some(health) = observed(generation, data) {
+        return health;
+    }
+
+    let replicas = int(data, "/spec/replicas").unwrap_or(1);
+    let ready = int(data, "/status/readyReplicas").unwrap_or(0);
+    let updated = int(data, "/status/updatedReplicas").unwrap_or(0);
+    let on_delete = str_at(data, "/spec/updateStrategy/type") == Some("OnDelete");
+    let revision_pending =
+        str_at(data, "/status/updateRevision") != str_at(data, "/status/currentRevision");
+
+    if !on_delete && revision_pending && updated < replicas {
+        ObjectHealth::Progressing(format!("{updated}/{replicas} replicas updated"))
+    } else if ready < replicas {
+        ObjectHealth::Progressing(format!("{ready}/{replicas} replicas ready"))
+    } else {
+        ObjectHealth::Healthy
+    }
+}
+
+fn daemon_set_health(generation: Option<i64>, data: &Value) -> ObjectHealth {
+    if let Some(health) = observed(generation, data) {
+        return health;
+    }
+
+    let desired = int(data, "/status/desiredNumberScheduled").unwrap_or(0);
+    let updated = int(data, "/status/updatedNumberScheduled").unwrap_or(0);
+    let available = int(data, "/status/numberAvailable").unwrap_or(0);
+    let on_delete = str_at(data, "/spec/updateStrategy/type") == Some("OnDelete");
+
+    if !on_delete && updated < desired {
+        ObjectHealth::Progressing(format!("{updated}/{desired} pods updated"))
+    } else if available < desired {
+        ObjectHealth::Progressing(format!("{available}/{desired} pods available"))
+    } else {
+        ObjectHealth::Healthy
+    }
+}
+
+fn job_health(data: &Value) -> ObjectHealth {
+    if condition_status(data, "Failed") == Some("True") {
+        ObjectHealth::Degraded(condition_message(data, "Failed"))
+    } else if condition_status(data, "Complete") == Some("True") {
+        ObjectHealth::Healthy
+    } else {
+        ObjectHealth::Progressing("job has not completed yet".to_string())
+    }
+}
+
+fn pvc_health(data: &Value) -> ObjectHealth {
+    match str_at(data, "/status/phase") {
+        Some("Bound") => ObjectHealth::Healthy,
+        Some("Lost") => ObjectHealth::Degraded("claim lost its volume".to_string()),
+        phase => ObjectHealth::Progressing(format!("claim is {}", phase.unwrap_or("Pending"))),
+    }
+}
+
+/// Health of resources we don't know about, based on the conventional `Ready` and `Stalled` conditions.
+/// Resources without such conditions are considered healthy as soon as they exist.
+fn generic_health(generation: Option<i64>, data: &Value) -> ObjectHealth {
+    if condition_status(data, "Stalled") == Some("True") {
+        return ObjectHealth::Degraded(condition_message(data, "Stalled"));
+    }
+    if let Some(health) = observed(generation, data) {
+        return health;
+    }
+    match condition_status(data, "Ready") {
+        None | Some("True") => ObjectHealth::Healthy,
+        Some(_) => ObjectHealth::Progressing(condition_message(data, "Ready")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kube::{
+        api::{ApiResource, DynamicObject, GroupVersionKind},
+        core::ObjectMeta,
+    };
+
+    fn applied(group: &str, kind: &str, generation: i64, data: Value) -> AppliedObject {
+        let resource = ApiResource::from_gvk(&GroupVersionKind::gvk(group, "v1", kind));
+        AppliedObject {
+            object: DynamicObject {
+                types: None,
+                metadata: ObjectMeta {
+                    name: Some("foo".to_string()),
+                    namespace: Some("test".to_string()),
+                    generation: Some(generation),
+                    ..Default::default()
+                },
+                data,
+            },
+            resource,
+        }
+    }
+
+    #[test]
+    fn deployment() {
+        let rolled_out = applied(
+            "apps",
+            "Deployment",
+            2,
+            serde_json::json!({
+                "spec": {"replicas": 2},
+                "status": {"observedGeneration": 2, "replicas": 2, "updatedReplicas": 2, "availableReplicas": 2},
+            }),
+        );
+        assert_eq!(assess(&[rolled_out]), Health::Healthy);
+
+        let rolling = applied(
+            "apps",
+            "Deployment",
+            3,
+            serde_json::json!({
+                "spec": {"replicas": 2},
+                "status": {"observedGeneration": 3, "replicas": 3, "updatedReplicas": 1, "availableReplicas": 2},
+            }),
+        );
+        assert_eq!(
+            assess(&[rolling]),
+            Health::Progressing(vec!["Deployment test/foo: 1/2 replicas updated".to_string()])
+        );
+
+        let stuck = applied(
+            "apps",
+            "Deployment",
+            3,
+            serde_json::json!({
+                "spec": {"replicas": 2},
+                "status": {
+                    "observedGeneration": 3,
+                    "conditions": [{
+                        "type": "Progressing",
+                        "status": "False",
+                        "reason": "ProgressDeadlineExceeded",
+                        "message": "ReplicaSet \"foo-123\" has timed out progressing.",
+                    }],
+                },
+            }),
+        );
+        assert_eq!(
+            assess(&[stuck]),
+            Health::Degraded(vec![
+                "Deployment test/foo: ReplicaSet \"foo-123\" has timed out progressing."
+                    .to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn degraded_wins_over_progressing() {
+        let pvc = applied(
+            "",
+            "PersistentVolumeClaim",
+            1,
+            serde_json::json!({"status": {"phase": "Pending"}}),
+        );
+        let job = applied(
+            "batch",
+            "Job",
+            1,
+            serde_json::json!({"status": {"conditions": [{"type": "Failed", "status": "True", "reason": "BackoffLimitExceeded"}]}}),
+        );
+        assert_eq!(
+            assess(&[pvc, job]),
+            Health::Degraded(vec!["Job test/foo: BackoffLimitExceeded".to_string()])
+        );
+    }
+
+    #[test]
+    fn custom_resource_ready_condition() {
+        let ready = applied(
+            "cert-manager.io",
+            "Certificate",
+            1,
+            serde_json::json!({"status": {"conditions": [{"type": "Ready", "status": "True"}]}}),
+        );
+        let no_status = applied("", "ConfigMap", 1, serde_json::json!({"data": {}}));
+        assert_eq!(assess(&[ready, no_status]), Health::Healthy);
+
+        let not_ready = applied(
+            "cert-manager.io",
+            "Certificate",
+            1,
+            serde_json::json!({"status": {"conditions": [{"type": "Ready", "status": "False", "message": "Issuing certificate"}]}}),
+        );
+        assert_eq!(
+            assess(&[not_ready]),
+            Health::Progressing(vec!["Certificate test/foo: Issuing certificate".to_string()])
+        );
+    }
+}
@@ -1,23 +1,39 @@
-use std::fs::File;
+use std::{
+    fs::{self, File},
+    io::{stdout, Write},
+    path::Path,
+};
 
 use anyhow::Result;
 use clap::Subcommand;
+use docker_credential::DockerCredential;
 use k8s_openapi::api::core::v1::ConfigMap;
 use kube::{Api, Client};
+use oci_distribution::{secrets::RegistryAuth, Reference};
 
-use crate::resources::AppInstance;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    engine::{RenderEngine, KUSTOMIZE_PACKAGE_DIR},
+    manifests,
+    resources::AppInstance,
+};
 
 /// Commands used by the kubit controller
 #[derive(Clone, Subcommand)]
 #[clap(hide = true)]
 pub enum Helper {
-    /// Fetch an AppInstance resource and output to a file.
+    /// Fetch an AppInstance resource and output to a file, as the overlay of the render engine.
     ///
     /// It removes the status field.
     FetchAppInstance {
         #[arg(long)]
         namespace: String,
 
+        #[arg(long, value_enum, default_value_t = RenderEngine::Kubecfg)]
+        engine: RenderEngine,
+
         #[arg(long, help = "output file")]
         output: String,
 
@@ -28,17 +44,58 @@ pub enum Helper {
         #[arg(long)]
         namespace: String,
 
+        #[arg(
+            long,
+            help = "data key holding the AppInstance",
+            default_value = "app-instance"
+        )]
+        key: String,
+
+        #[arg(long, value_enum, default_value_t = RenderEngine::Kubecfg)]
+        engine: RenderEngine,
+
         #[arg(long, help = "output file")]
         output: String,
 
         config_map: String,
     },
+
+    /// Fetch the manifests of a plain manifest bundle, substituting their variables, and output
+    /// them as a single YAML stream to `manifests.yaml` in a directory, or to stdout.
+    FetchManifests {
+        #[arg(long, help = "JSON file holding the values of the variables")]
+        values: Option<String>,
+
+        #[arg(long, help = "output directory")]
+        output: Option<String>,
+
+        #[arg(long, help = "pull the bundle anonymously")]
+        skip_auth: bool,
+
+        image: String,
+    },
+
+    /// Unpack the kustomization root of a package into a directory, next to the kustomization
+    /// generated for the instance.
+    FetchKustomization {
+        #[arg(long, help = "generated kustomization")]
+        overlay: String,
+
+        #[arg(long, help = "output directory")]
+        output: String,
+
+        #[arg(long, help = "pull the package anonymously")]
+        skip_auth: bool,
+
+        image: String,
+    },
 }
 
 pub async fn run(helper: &Helper) -> Result<()> {
     match helper {
         Helper::FetchAppInstance {
             namespace,
+            engine,
             app_instance,
             output,
         } => {
@@ -54,11 +111,13 @@ pub async fn run(helper: &Helper) -> Result<()> {
                 .and_then(|labels| labels.remove("applyset.kubernetes.io/part-of"));
 
             let file = File::create(output)?;
-            serde_json::to_writer_pretty(file, &app_instance)?;
+            engine.write_overlay(&app_instance, file)?;
         }
 
         Helper::FetchAppInstanceFromConfigMap {
             namespace,
+            key,
+            engine,
             config_map,
             output,
         } => {
@@ -71,16 +130,75 @@ pub async fn run(helper: &Helper) -> Result<()> {
                 config_map
             ))?;
 
-            let app_instance = data.get("app-instance").ok_or(anyhow::anyhow!(
-                "ConfigMap {} data did not have an app-instance field",
-                config_map
+            let app_instance = data.get(key).ok_or(anyhow::anyhow!(
+                "ConfigMap {} data did not have an {} field",
+                config_map,
+                key
             ))?;
 
             let ai: AppInstance = serde_yaml::from_str(app_instance)?;
 
             let file = File::create(output)?;
-            serde_yaml::to_writer(file, &ai)?;
+            engine.write_overlay(&ai, file)?;
+        }
+
+        Helper::FetchManifests {
+            values,
+            output,
+            skip_auth,
+            image,
+        } => {
+            let values = match values {
+                Some(values) => serde_json::from_reader(File::open(values)?)?,
+                None => serde_json::Value::Null,
+            };
+            let auth = if *skip_auth {
+                RegistryAuth::Anonymous
+            } else {
+                registry_auth(image)?
+            };
+            let files = manifests::fetch(image, &auth).await?;
+            let rendered = manifests::render(&files, &values);
+
+            match output {
+                Some(dir) => {
+                    File::create(format!("{dir}/manifests.yaml"))?.write_all(rendered.as_bytes())?
+                }
+                None => stdout().lock().write_all(rendered.as_bytes())?,
+            }
+        }
+
+        Helper::FetchKustomization {
+            overlay,
+            output,
+            skip_auth,
+            image,
+        } => {
+            let auth = if *skip_auth {
+                RegistryAuth::Anonymous
+            } else {
+                registry_auth(image)?
+            };
+            let output = Path::new(output);
+            manifests::unpack(image, &auth, &output.join(KUSTOMIZE_PACKAGE_DIR)).await?;
+            fs::copy(overlay, output.join("kustomization.yaml"))?;
         }
     }
     Ok(())
 }
+
+/// Returns the credentials for the registry of the image found in the docker config, which
+/// in the jobs is projected from the image pull secrets of the instance, if any.
+fn registry_auth(image: &str) -> Result<RegistryAuth> {
+    let reference: Reference = image.parse()?;
+    match docker_credential::get_credential(reference.registry()) {
+        Ok(DockerCredential::UsernamePassword(username, password)) => {
+            Ok(RegistryAuth::Basic(username, password))
+        }
+        Ok(DockerCredential::IdentityToken(_)) => anyhow::bail!("unsupported docker credentials"),
+        Err(error) => {
+            warn!(%error, "no credentials found, pulling anonymously");
+            Ok(RegistryAuth::Anonymous)
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeSet;
+
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+    api::{ListParams, ObjectList},
+    discovery::{ApiCapabilities, ApiResource, Scope},
+    Api, Client, Discovery, ResourceExt,
+};
+
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    resources::{AppInstance, InventoryEntry},
+    Result,
+};
+
+const APPLYSET_ID_LABEL: &str = "applyset.kubernetes.io/id";
+const APPLYSET_PART_OF_LABEL: &str = "applyset.kubernetes.io/part-of";
+const APPLYSET_GROUP_KINDS_ANNOTATION: &str = "applyset.kubernetes.io/contains-group-kinds";
+const APPLYSET_GROUP_RESOURCES_ANNOTATION: &str = "applyset.kubernetes.io/contains-group-resources";
+const APPLYSET_ADDITIONAL_NAMESPACES_ANNOTATION: &str =
+    "applyset.kubernetes.io/additional-namespaces";
+
+/// A live object that belongs to the applyset of an AppInstance.
+#[derive(Debug, Clone)]
+pub struct AppliedObject {
+    pub resource: ApiResource,
+    pub object: kube::api::DynamicObject,
+}
+
+impl AppliedObject {
+    pub fn inventory_entry(&self) -> InventoryEntry {
+        InventoryEntry {
+            group: self.resource.group.clone(),
+            version: self.resource.version.clone(),
+            kind: self.resource.kind.clone(),
+            namespace: self.object.namespace(),
+            name: self.object.name_any(),
+        }
+    }
+}
+
+/// Returns the sorted inventory of the given applied objects.
+pub fn inventory(objects: &[AppliedObject]) -> Vec<InventoryEntry> {
+    objects
+        .iter()
+        .map(AppliedObject::inventory_entry)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// A group-qualified kind (or resource, for applysets written by older kubectl versions)
+/// as listed in the applyset parent annotations.
+#[derive(Debug, PartialEq)]
+enum GroupMember {
+    Kind { group: String, kind: String },
+    Resource { group: String, plural: String },
+}
+
+impl GroupMember {
+    fn group(&self) -> &str {
+        match self {
+            GroupMember::Kind { group, .. } | GroupMember::Resource { group, .. } => group,
+        }
+    }
+
+    fn matches(&self, resource: &ApiResource) -> bool {
+        match self {
+            GroupMember::Kind { kind, .. } => &resource.kind == kind,
+            GroupMember::Resource { plural, .. } => &resource.plural == plural,
+        }
+    }
+}
+
+/// Parses a comma separated list of `Name.group` items; core group items have no group suffix.
+fn parse_group_members(
+    value: &str,
+    mk: impl Fn(String, String) -> GroupMember,
+) -> Vec<GroupMember> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.split_once('.') {
+            Some((name, group)) => mk(group.to_string(), name.to_string()),
+            None => mk(String::new(), s.to_string()),
+        })
+        .collect()
+}
+
+/// Lists the live objects that belong to the applyset of the given AppInstance,
+/// including the ones living outside the AppInstance namespace.
+///
+/// The applyset parent is the Secret that `kubectl apply --applyset` maintains next to the AppInstance.
+/// If it doesn't exist (e.g. nothing has been applied yet) the inventory is empty.
+pub async fn applyset_objects(
+    client: &Client,
+    app_instance: &AppInstance,
+) -> Result<Vec<AppliedObject>> {
+    let ns = app_instance.namespace_any();
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), &ns);
+    let Some(parent) = secrets.get_opt(&app_instance.name_any()).await? else {
+        return Ok(vec![]);
+    };
+    let Some(applyset_id) = parent.labels().get(APPLYSET_ID_LABEL) else {
+        return Ok(vec![]);
+    };
+
+    let annotations = parent.annotations();
+    let mut members = vec![];
+    if let Some(kinds) = annotations.get(APPLYSET_GROUP_KINDS_ANNOTATION) {
+        members.extend(parse_group_members(kinds, |group, kind| {
+            GroupMember::Kind { group, kind }
+        }));
+    }
+    if let Some(resources) = annotations.get(APPLYSET_GROUP_RESOURCES_ANNOTATION) {
+        members.extend(parse_group_members(resources, |group, plural| {
+            GroupMember::Resource { group, plural }
+        }));
+    }
+    if members.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut namespaces = vec![ns];
+    if let Some(additional) = annotations.get(APPLYSET_ADDITIONAL_NAMESPACES_ANNOTATION) {
+        namespaces.extend(
+            additional
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        );
+    }
+
+    let groups: Vec<&str> = members
+        .iter()
+        .map(GroupMember::group)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let discovery = Discovery::new(client.clone()).filter(&groups).run().await?;
+
+    let list_params =
+        ListParams::default().labels(&format!("{APPLYSET_PART_OF_LABEL}={applyset_id}"));
+    let mut objects = vec![];
+    for member in &members {
+        let Some((resource, capabilities)) = resolve(&discovery, member) else {
+            warn!(
+                ?member,
+                "applyset member not served by the API server, skipping"
+            );
+            continue;
+        };
+
+        let lists: Vec<ObjectList<kube::api::DynamicObject>> = match capabilities.scope {
+            Scope::Cluster => {
+                let api = Api::all_with(client.clone(), &resource);
+                vec![api.list(&list_params).await?]
+            }
+            Scope::Namespaced => {
+                let mut lists = vec![];
+                for ns in &namespaces {
+                    let api = Api::namespaced_with(client.clone(), ns, &resource);
+                    lists.push(api.list(&list_params).await?);
+                }
+                lists
+            }
+        };
+
+        objects.extend(
+            lists
+                .into_iter()
+                .flat_map(|list| list.items)
+                .map(|object| AppliedObject {
+                    resource: resource.clone(),
+                    object,
+                }),
+        );
+    }
+
+    Ok(objects)
+}
+
+fn resolve(discovery: &Discovery, member: &GroupMember) -> Option<(ApiResource, ApiCapabilities)> {
+    discovery
+        .get(member.group())?
+        .recommended_resources()
+        .into_iter()
+        .find(|(resource, _)| member.matches(resource))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_members() {
+        let kinds = parse_group_members(
+            "Deployment.apps, Service,,Certificate.cert-manager.io",
+            |group, kind| GroupMember::Kind { group, kind },
+        );
+        assert_eq!(
+            kinds,
+            vec![
+                GroupMember::Kind {
+                    group: "apps".to_string(),
+                    kind: "Deployment".to_string()
+                },
+                GroupMember::Kind {
+                    group: "".to_string(),
+                    kind: "Service".to_string()
+                },
+                GroupMember::Kind {
+                    group: "cert-manager.io".to_string(),
+                    kind: "Certificate".to_string()
+                },
+            ]
+        );
+
+        let resources = parse_group_members("statefulsets.apps", |group, plural| {
+            GroupMember::Resource { group, plural }
+        });
+        assert_eq!(
+            resources,
+            vec![GroupMember::Resource {
+                group: "apps".to_string(),
+                plural: "statefulsets".to_string()
+            }]
+        );
+    }
+}
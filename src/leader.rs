@@ -0,0 +1,194 @@
+use std::{borrow::Cow, future::Future, sync::Arc, time::Duration};
+
+use k8s_openapi::api::coordination::v1::Lease;
+use kube::{api::PostParams, core::ObjectMeta, Api, Client};
+use kubert::{
+    lease::{Claim, LeaseParams},
+    shutdown,
+};
+use tokio::sync::watch;
+
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+use crate::{Error, Result};
+
+/// Configures the election of the replica that runs the controller.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct LeaderElectionArgs {
+    /// Only reconcile while holding a Lease, so that more than one replica of the controller
+    /// can run at the same time, with the others on standby.
+    #[clap(long, env = "KUBIT_LEADER_ELECTION", default_value = "false")]
+    pub leader_election: bool,
+
+    /// Name of the Lease used for the leader election
+    #[clap(long, env = "KUBIT_LEASE_NAME", default_value = "kubit")]
+    pub lease_name: String,
+
+    /// Namespace of the Lease used for the leader election
+    #[clap(long, env = "KUBIT_LEASE_NAMESPACE", default_value = "kubit")]
+    pub lease_namespace: String,
+
+    /// Identity of this replica in the leader election; defaults to the hostname, i.e. the pod name
+    #[clap(long, env = "KUBIT_LEASE_CLAIMANT")]
+    pub lease_claimant: Option<String>,
+
+    /// How long the leader holds the Lease without renewing it, i.e. how long it takes for a
+    /// standby replica to take over when the leader dies
+    #[clap(long, default_value = "15s", value_parser = parse_duration)]
+    pub lease_duration: Duration,
+
+    /// How long before the Lease expires the leader renews it
+    #[clap(long, default_value = "5s", value_parser = parse_duration)]
+    pub lease_renew_grace_period: Duration,
+}
+
+fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    crate::duration::parse(s).map_err(|e| e.to_string())
+}
+
+/// A replica taking part in the leader election.
+pub struct Candidate {
+    claimant: String,
+    claims: watch::Receiver<Arc<Claim>>,
+}
+
+/// Starts taking part in the leader election.
+///
+/// This only claims the Lease: waiting for it is left to [`run_when_elected`], so that the
+/// runtime, and its admin server with it, runs on standby replicas too.
+pub async fn candidate<S>(rt: &kubert::Runtime<S>, args: LeaderElectionArgs) -> Result<Candidate> {
+    let claimant = match args.lease_claimant {
+        Some(claimant) => claimant,
+        None => std::env::var("HOSTNAME").unwrap_or_else(|_| "kubit".to_string()),
+    };
+
+    ensure_lease(rt.client(), &args.lease_namespace, &args.lease_name).await?;
+
+    let (claims, _task) = rt
+        .spawn_lease(LeaseParams {
+            name: args.lease_name,
+            namespace: args.lease_namespace,
+            claimant: claimant.clone(),
+            lease_duration: args.lease_duration,
+            renew_grace_period: args.lease_renew_grace_period,
+            field_manager: Some(Cow::Borrowed("kubit")),
+        })
+        .await?;
+
+    Ok(Candidate { claimant, claims })
+}
+
+impl Candidate {
+    /// Waits until this replica becomes the leader.
+    ///
+    /// Returns a future that completes when this replica stops being the leader.
+    pub async fn acquire(self) -> Result<impl Future<Output = ()>> {
+        let Candidate {
+            claimant,
+            mut claims,
+        } = self;
+        info!(claimant, "waiting for leadership");
+        if !wait_for(&mut claims, |claim| claim.is_current_for(&claimant)).await {
+            return Err(Error::LeaderElectionStopped);
+        }
+        info!(claimant, "acquired leadership");
+
+        Ok(async move {
+            wait_for(&mut claims, |claim| !claim.is_current_for(&claimant)).await;
+            warn!(claimant, "lost leadership");
+        })
+    }
+}
+
+/// Runs `controller` once `elected` resolves, until it completes or the leadership is lost,
+/// in which case the process exits so that it restarts as a standby.
+///
+/// Returns without running the controller if the runtime shuts down first.
+pub async fn run_when_elected<E, L, C>(
+    elected: E,
+    shutdown: shutdown::Watch,
+    controller: C,
+) -> Result<()>
+where
+    E: Future<Output = Result<L>>,
+    L: Future<Output = ()>,
+    C: Future<Output = Result<()>>,
+{
+    let leadership_lost = tokio::select! {
+        leadership_lost = elected => leadership_lost?,
+        _ = shutdown.signaled() => {
+            info!("shutting down on standby");
+            return Ok(());
+        }
+    };
+    tokio::select! {
+        res = controller => res,
+        _ = leadership_lost => {
+            // Let the replica restart as a standby, another one is reconciling now.
+            error!("lost leadership, exiting");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Waits until the claim satisfies `f`; returns false if the lease task stopped before that,
+/// in which case nobody renews our claim anymore.
+async fn wait_for(claims: &mut watch::Receiver<Arc<Claim>>, f: impl Fn(&Claim) -> bool) -> bool {
+    claims.wait_for(|claim| f(claim)).await.is_ok()
+}
+
+/// Creates the Lease if it doesn't exist yet.
+async fn ensure_lease(client: Client, namespace: &str, name: &str) -> Result<()> {
+    let api: Api<Lease> = Api::namespaced(client, namespace);
+    if api.get_opt(name).await?.is_some() {
+        return Ok(());
+    }
+
+    let lease = Lease {
+        metadata: ObjectMeta {
+            name: Some(name.to_string()),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        spec: Some(Default::default()),
+    };
+    match api.create(&PostParams::default(), &lease).await {
+        Ok(_) => Ok(()),
+        // Another replica created it in the meantime.
+        Err(kube::Error::Api(ae)) if ae.code == 409 => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn standby_shuts_down() {
+        let (signal, shutdown) = drain::channel();
+        let standby = tokio::spawn(run_when_elected(
+            std::future::pending::<Result<std::future::Pending<()>>>(),
+            shutdown,
+            async { panic!("a standby replica must not run the controller") },
+        ));
+
+        tokio::time::timeout(Duration::from_secs(5), signal.drain())
+            .await
+            .expect("the standby replica should release the shutdown");
+        standby.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn leader_runs_controller() {
+        let (_signal, shutdown) = drain::channel();
+        let ran = run_when_elected(
+            async { Ok(std::future::pending::<()>()) },
+            shutdown,
+            async { Ok(()) },
+        )
+        .await;
+        assert!(ran.is_ok());
+    }
+}
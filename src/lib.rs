@@ -25,6 +25,24 @@ pub enum Error {
     #[error("Error decoding kubecfg pack metadata JSON: {0}")]
     DecodeKubecfgPackageMetadata(serde_json::Error),
 
+    #[error("Package spec doesn't match the package schema: {}", .0.join("; "))]
+    InvalidSpec(Vec<String>),
+
+    #[error("The package doesn't declare the permissions it needs under kubit.kubecfg.dev/v1alpha1, and the controller doesn't allow broad applier roles")]
+    UndeclaredPermissions,
+
+    #[error("spec.serviceAccountName is required by the controller")]
+    ServiceAccountNameRequired,
+
+    #[error("Unsupported render engine: {0}")]
+    UnsupportedRenderEngine(String),
+
+    #[error("The bundle doesn't contain any manifest")]
+    EmptyManifestsBundle,
+
+    #[error("Invalid manifests bundle: {0}")]
+    InvalidManifestsBundle(String),
+
     #[error("Error rendering spec back as JSON: {0}")]
     RenderOverlay(serde_json::Error),
 
@@ -40,9 +58,6 @@ pub enum Error {
     #[error("ConfigMap is required")]
     ConfigMapRequired,
 
-    #[error(".spec.imagePullSecret currently requires to have exactly one pull secret")]
-    UnsupportedMultipleImagePullSecrets,
-
     #[error("Image pull secret doesn't contain .dockerconfigjson")]
     NoDockerConfigJsonInImagePullSecret,
 
@@ -65,6 +80,24 @@ pub enum Error {
 
     #[error("The ConfigMap could not be converted to an AppInstance: {0}")]
     InvalidConfigMap(String),
+
+    #[error("Lease error: {0}")]
+    Lease(#[from] kubert::lease::Error),
+
+    #[error("The leader election stopped before this replica became the leader")]
+    LeaderElectionStopped,
+
+    #[error("Secret {0} doesn't contain the kubeconfig key {1}")]
+    NoKubeconfigInSecret(String, String),
+
+    #[error("Invalid kubeconfig: {0}")]
+    Kubeconfig(#[from] kube::config::KubeconfigError),
+
+    #[error("Invalid duration {0:?}, expected e.g. \"90s\", \"10m\" or \"1h30m\"")]
+    InvalidDuration(String),
+
+    #[error("Interval {0:?} is too short, it must be at least 1m")]
+    IntervalTooShort(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -76,12 +109,24 @@ pub mod controller;
 pub mod resources;
 
 pub mod apply;
+pub mod backoff;
 pub mod delete;
+pub mod drift;
+pub mod duration;
+pub mod engine;
+pub mod health;
 pub mod helpers;
+pub mod inventory;
+pub mod leader;
 pub mod local;
+pub mod logs;
+pub mod manifests;
 pub mod metadata;
+pub mod metrics;
+pub mod redact;
 pub mod render;
 mod scripting;
+pub mod webhook;
 
 mod docker_config;
 mod oci;
@@ -1,6 +1,9 @@
 use anyhow::{bail, Result};
 use clap::Subcommand;
-use kube::ResourceExt;
+use kube::{
+    config::{KubeConfigOptions, Kubeconfig},
+    ResourceExt,
+};
 use std::fs::{self, File};
 use std::io;
 use std::io::{stdout, IsTerminal, Read, Write};
@@ -13,7 +16,9 @@ use crate::delete::cleanup_hack_resource_name;
 use crate::Error;
 use crate::{
     apply::{self, KUBIT_APPLIER_FIELD_MANAGER},
-    delete, render,
+    controller, delete,
+    engine::{self, RenderEngine, RenderImages},
+    inventory, render,
     resources::AppInstance,
     scripting::Script,
 };
@@ -55,6 +60,10 @@ pub enum Local {
         /// Override the image for kubecfg
         #[clap(long, default_value = render::DEFAULT_KUBECFG_IMAGE)]
         kubecfg_image: String,
+
+        /// Override the image for helm, used to render Helm charts
+        #[clap(long, default_value = engine::DEFAULT_HELM_IMAGE)]
+        helm_image: String,
     },
 
     /// Delete the resources created by a packaged AppInstance.
@@ -65,7 +74,7 @@ pub enum Local {
         /// Path to the file containing a (YAML) AppInstance manifest.
         app_instance: String,
 
-        /// Dry run
+        /// Dry run. `render` lists the resources that would be pruned.
         #[clap(long)]
         dry_run: Option<DryRun>,
 
@@ -105,6 +114,7 @@ pub async fn run(local: &Local, impersonate_user: &Option<String>) -> Result<()>
             docker,
             apply_step_image,
             kubecfg_image,
+            helm_image,
         } => {
             apply(
                 app_instance,
@@ -115,7 +125,13 @@ pub async fn run(local: &Local, impersonate_user: &Option<String>) -> Result<()>
                 *docker,
                 *skip_auth,
                 apply_step_image.to_string(),
-                kubecfg_image.to_string(),
+                RenderImages {
+                    kubecfg: kubecfg_image.to_string(),
+                    helm: helm_image.to_string(),
+                    // Bundles are fetched by this very binary.
+                    kubit: controller::KUBIT_IMAGE.to_string(),
+                    kubectl: apply_step_image.to_string(),
+                },
             )
             .await?;
         }
@@ -123,7 +139,7 @@ pub async fn run(local: &Local, impersonate_user: &Option<String>) -> Result<()>
             app_instance,
             docker,
             dry_run,
-        } => delete(app_instance, *docker, dry_run).await?,
+        } => delete(app_instance, impersonate_user, *docker, dry_run).await?,
     };
     Ok(())
 }
@@ -187,7 +203,7 @@ pub async fn apply(
     docker: bool,
     skip_auth: bool,
     kubectl_image: String,
-    kubecfg_image: String,
+    render_images: RenderImages,
 ) -> Result<()> {
     let (output, path) = get_script(dry_run)?;
 
@@ -211,7 +227,7 @@ pub async fn apply(
             docker,
             skip_auth,
             kubectl_image.clone(),
-            kubecfg_image.clone(),
+            render_images.clone(),
         )
         .await?;
         if !confirm_continue() {
@@ -229,7 +245,7 @@ pub async fn apply(
         skip_auth,
         path,
         kubectl_image,
-        kubecfg_image,
+        render_images,
     )
     .await
 }
@@ -293,7 +309,7 @@ async fn write_apply_script(
     skip_auth: bool,
     path: Option<PathBuf>,
     kubectl_image: String,
-    kubecfg_image: String,
+    render_images: RenderImages,
 ) -> Result<()> {
     let mut steps: Vec<Script> = vec![];
 
@@ -301,15 +317,18 @@ async fn write_apply_script(
         steps.extend([Script::from_str("export KUBECTL_APPLYSET=true")]);
     }
 
-    steps.extend([render::script(
-        &app_instance,
-        overlay_file_name,
-        None,
-        docker,
-        skip_auth,
-        kubecfg_image,
-    )
-    .await?
+    let engine = RenderEngine::for_local_package(&app_instance, skip_auth).await;
+    let overlay_file = engine.local_overlay(&app_instance, overlay_file_name)?;
+    steps.extend([engine
+        .script(
+            &app_instance,
+            &overlay_file,
+            None,
+            docker,
+            skip_auth,
+            &render_images,
+        )
+        .await?
         | match dry_run {
             Some(DryRun::Render) => Script::from_str("cat"),
             Some(DryRun::Diff) => diff(&app_instance)?,
@@ -335,6 +354,7 @@ async fn write_apply_script(
 async fn write_delete_script(
     app_instance: AppInstance,
     mut output: Box<dyn WriteClose>,
+    impersonate_user: &Option<String>,
     docker: bool,
     path: Option<PathBuf>,
 ) -> Result<()> {
@@ -346,14 +366,30 @@ async fn write_delete_script(
         cleanup_hack_resource_name(&app_instance.name_any())
     );
 
+    let mut access = delete::ClusterAccess {
+        impersonate_user: impersonate_user.clone(),
+        ..Default::default()
+    };
+    if let Some(kubeconfig) = target_kubeconfig(&app_instance, impersonate_user).await? {
+        let kubeconfig_path = tmp_dir.path().join("kubeconfig");
+        fs::write(&kubeconfig_path, serde_yaml::to_string(&kubeconfig)?)?;
+        access.kubeconfig = Some(kubeconfig_path.display().to_string());
+    }
+
     if !docker {
         steps.extend([Script::from_str("export KUBECTL_APPLYSET=true")]);
     }
 
     steps.extend([
-        delete::setup_script(&app_instance, &app_instance.name_any(), output_path, docker)?,
-        delete::script(&app_instance, output_path, docker)?,
-        delete::post_pruning_script(&app_instance, &app_instance.name_any(), docker)?,
+        delete::setup_script(
+            &app_instance,
+            &app_instance.name_any(),
+            output_path,
+            &access,
+            docker,
+        )?,
+        delete::script(&app_instance, output_path, &access, docker)?,
+        delete::post_pruning_script(&app_instance, &app_instance.name_any(), &access, docker)?,
     ]);
 
     let script: Script = steps.into_iter().sum();
@@ -370,6 +406,35 @@ async fn write_delete_script(
     Ok(())
 }
 
+/// Connection settings of the cluster the deletion acts on: the one of the given kubeconfig, or
+/// of the current context, as the impersonated user.
+async fn cluster_config(
+    kubeconfig: Option<Kubeconfig>,
+    impersonate_user: &Option<String>,
+) -> Result<kube::Config> {
+    let mut config = match kubeconfig {
+        Some(kubeconfig) => {
+            kube::Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default()).await?
+        }
+        None => kube::Config::infer().await?,
+    };
+    config.auth_info.impersonate = impersonate_user.clone();
+    Ok(config)
+}
+
+/// Reads the kubeconfig of the cluster the AppInstance is installed in, if it targets another
+/// cluster than the current context.
+async fn target_kubeconfig(
+    app_instance: &AppInstance,
+    impersonate_user: &Option<String>,
+) -> Result<Option<Kubeconfig>> {
+    if app_instance.spec.target_cluster.is_none() {
+        return Ok(None);
+    }
+    let client = kube::Client::try_from(cluster_config(None, impersonate_user).await?)?;
+    Ok(controller::target_kubeconfig(&client, app_instance).await?)
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn prediff(
     overlay_file_name: &str,
@@ -379,7 +444,7 @@ async fn prediff(
     docker: bool,
     skip_auth: bool,
     kubectl_image: String,
-    kubecfg_image: String,
+    render_images: RenderImages,
 ) -> Result<()> {
     let (output, path) = get_script(dry_run)?;
 
@@ -400,7 +465,7 @@ async fn prediff(
         skip_auth,
         path,
         kubectl_image,
-        kubecfg_image,
+        render_images,
     )
     .await
 }
@@ -425,10 +490,27 @@ pub fn confirm_continue() -> bool {
     matches!(buffer[0], b'y' | b'Y')
 }
 
-pub async fn delete(app_instance: &str, docker: bool, dry_run: &Option<DryRun>) -> Result<()> {
+pub async fn delete(
+    app_instance: &str,
+    impersonate_user: &Option<String>,
+    docker: bool,
+    dry_run: &Option<DryRun>,
+) -> Result<()> {
     match dry_run {
-        Some(DryRun::Render | DryRun::Diff) => {
-            Err(Error::UnsupportedDryRunOption(dry_run.clone().unwrap()).into())
+        Some(DryRun::Diff) => Err(Error::UnsupportedDryRunOption(dry_run.clone().unwrap()).into()),
+        Some(DryRun::Render) => {
+            let file = File::open(app_instance)?;
+            let app_instance: AppInstance = serde_yaml::from_reader(file)?;
+
+            let kubeconfig = target_kubeconfig(&app_instance, impersonate_user).await?;
+            let client =
+                kube::Client::try_from(cluster_config(kubeconfig, impersonate_user).await?)?;
+            let objects = inventory::applyset_objects(&client, &app_instance).await?;
+            for entry in inventory::inventory(&objects) {
+                println!("{entry}");
+            }
+
+            Ok(())
         }
         Some(DryRun::Script) | None => {
             let (output, path) = get_script(dry_run)?;
@@ -436,7 +518,7 @@ pub async fn delete(app_instance: &str, docker: bool, dry_run: &Option<DryRun>)
             let file = File::open(app_instance)?;
             let app_instance: AppInstance = serde_yaml::from_reader(file)?;
 
-            write_delete_script(app_instance, output, docker, path).await?;
+            write_delete_script(app_instance, output, impersonate_user, docker, path).await?;
 
             Ok(())
         }
@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, HashMap};
+
+use k8s_openapi::chrono::{DateTime, Utc};
+
+/// Upper bound of the log of a single container stored in the logs Secret.
+const MAX_CONTAINER_LOG_BYTES: usize = 256 * 1024;
+
+/// Upper bound of the logs of a run, whatever its number of containers, so that the last run
+/// always fits in the logs Secret.
+const MAX_RUN_BYTES: usize = 512 * 1024;
+
+/// Upper bound of the content of the logs Secret, well below the 1MiB limit of Secrets.
+const MAX_SECRET_BYTES: usize = 900 * 1024;
+
+/// How the logs of the jobs are retained.
+#[derive(Debug, Clone)]
+pub struct LogsConfig {
+    /// Number of trailing lines of each container log kept in the status.
+    pub tail_lines: usize,
+    /// Number of runs whose full logs are kept in the logs Secret.
+    pub history: usize,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            tail_lines: 20,
+            history: 5,
+        }
+    }
+}
+
+/// Returns the last `lines` lines of a log.
+pub fn tail_lines(log: &str, lines: usize) -> &str {
+    if lines == 0 {
+        return "";
+    }
+    let trimmed = log.trim_end_matches('\n');
+    match trimmed.rmatch_indices('\n').nth(lines - 1) {
+        Some((idx, _)) => &log[idx + 1..],
+        None => log,
+    }
+}
+
+/// Returns at most the last `max` bytes of a log, starting at a line boundary if possible.
+fn tail_bytes(log: &str, max: usize) -> &str {
+    if log.len() <= max {
+        return log;
+    }
+    let mut start = log.len() - max;
+    while !log.is_char_boundary(start) {
+        start += 1;
+    }
+    let tail = &log[start..];
+    if log.as_bytes()[start - 1] == b'\n' {
+        return tail;
+    }
+    match tail.find('\n') {
+        Some(idx) if idx + 1 < tail.len() => &tail[idx + 1..],
+        _ => tail,
+    }
+}
+
+/// Renders the full logs of a run as stored in the logs Secret.
+///
+/// The logs are truncated from the start, to at most [`MAX_RUN_BYTES`] in total once rendered.
+pub fn render_run(logs: &HashMap<String, String>) -> Vec<u8> {
+    let mut max = MAX_CONTAINER_LOG_BYTES.min(MAX_RUN_BYTES / logs.len().max(1));
+    loop {
+        let tails: BTreeMap<_, _> = logs
+            .iter()
+            .map(|(container, log)| (container, tail_bytes(log, max)))
+            .collect();
+        let rendered = serde_json::to_vec(&tails).expect("cannot render basic json");
+        // Escaping can make the rendered logs larger than their tails.
+        if rendered.len() <= MAX_RUN_BYTES || max == 0 {
+            return rendered;
+        }
+        max = max * MAX_RUN_BYTES / rendered.len();
+    }
+}
+
+/// Returns the key of the logs of a job in the logs Secret.
+///
+/// The UID of the job tells apart the runs that finished within the same second, while the
+/// time keeps the keys sorting chronologically.
+pub fn run_key(finished_at: DateTime<Utc>, job_uid: &str) -> String {
+    format!("{}-{job_uid}.json", finished_at.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Adds a run to the history of runs, keyed by something that sorts chronologically,
+/// and drops the oldest runs in excess of `max_runs` or of the size limit of a Secret.
+///
+/// The new run is always kept.
+pub fn add_run(
+    history: &mut BTreeMap<String, Vec<u8>>,
+    key: String,
+    run: Vec<u8>,
+    max_runs: usize,
+) {
+    history.insert(key.clone(), run);
+
+    let mut size = 0;
+    let mut kept = 0;
+    let keys: Vec<_> = history.keys().rev().cloned().collect();
+    for k in keys {
+        size += history[&k].len();
+        kept += 1;
+        if k != key && (kept > max_runs || size > MAX_SECRET_BYTES) {
+            history.remove(&k);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tails() {
+        assert_eq!(tail_lines("a\nb\nc\n", 2), "b\nc\n");
+        assert_eq!(tail_lines("a\nb\nc", 2), "b\nc");
+        assert_eq!(tail_lines("a\nb\nc", 5), "a\nb\nc");
+        assert_eq!(tail_lines("a\nb\nc", 0), "");
+
+        assert_eq!(tail_bytes("aaaa\nbb\ncc\n", 6), "bb\ncc\n");
+        assert_eq!(tail_bytes("aaaa\nbb\ncc\n", 5), "cc\n");
+        assert_eq!(tail_bytes("short", 6), "short");
+        assert_eq!(tail_bytes("ééé", 3), "é");
+    }
+
+    #[test]
+    fn bounded_run() {
+        let line = "x".repeat(99) + "\n";
+        let logs: HashMap<_, _> = (0..8)
+            .map(|i| (format!("container-{i}"), line.repeat(5000)))
+            .collect();
+        let run = render_run(&logs);
+        assert!(run.len() <= MAX_RUN_BYTES);
+        let rendered: HashMap<String, String> = serde_json::from_slice(&run).unwrap();
+        assert_eq!(rendered.len(), 8);
+        assert!(rendered["container-0"].ends_with(&line));
+        assert!(rendered["container-0"].len() > MAX_RUN_BYTES / 16);
+
+        let escaped = HashMap::from([("apply".to_string(), "\u{1}".repeat(MAX_RUN_BYTES))]);
+        assert!(render_run(&escaped).len() <= MAX_RUN_BYTES);
+    }
+
+    #[test]
+    fn run_keys() {
+        let now = Utc::now();
+        let first = run_key(now, "3a0b6c1e-0f3d-4f0e-9d0c-6a3d1c2b4e5f");
+        let second = run_key(now, "b1f0e2d3-7c4a-4b5e-8f6d-9e0a1b2c3d4e");
+        assert_ne!(first, second);
+        assert!(first.ends_with("-3a0b6c1e-0f3d-4f0e-9d0c-6a3d1c2b4e5f.json"));
+
+        let later = run_key(now + std::time::Duration::from_secs(1), "0");
+        assert!(later > first && later > second);
+    }
+
+    #[test]
+    fn bounded_history() {
+        let mut history = BTreeMap::new();
+        for run in ["20240101T000000Z", "20240102T000000Z", "20240103T000000Z"] {
+            add_run(&mut history, run.to_string(), vec![0; 10], 2);
+        }
+        assert_eq!(
+            history.keys().collect::<Vec<_>>(),
+            ["20240102T000000Z", "20240103T000000Z"]
+        );
+
+        add_run(
+            &mut history,
+            "20240104T000000Z".to_string(),
+            vec![0; MAX_SECRET_BYTES],
+            2,
+        );
+        assert_eq!(history.keys().collect::<Vec<_>>(), ["20240104T000000Z"]);
+    }
+}
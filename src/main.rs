@@ -4,12 +4,57 @@ use std::{
     fs::File,
     io::{stdout, Write},
     path::PathBuf,
+    time::Duration,
 };
 
-use clap::{Parser, Subcommand};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
 use kube::CustomResourceExt;
+use regex::Regex;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use kubit::{apply, controller, helpers, local, metadata, render, resources::AppInstance};
+use kubit::{
+    apply,
+    backoff::BackoffConfig,
+    controller,
+    engine::{self, RenderImages},
+    helpers, leader, local,
+    logs::LogsConfig,
+    metadata, metrics,
+    redact::Redactor,
+    render,
+    resources::{AppInstance, JobTemplate},
+    webhook,
+};
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    kubit::duration::parse(s).map_err(|e| e.to_string())
+}
+
+/// Retries must not be delayed by zero, lest failing instances be retried in a tight loop.
+fn parse_backoff_initial(s: &str) -> Result<Duration, String> {
+    match parse_duration(s)? {
+        Duration::ZERO => Err("must be greater than zero".to_string()),
+        initial => Ok(initial),
+    }
+}
+
+fn parse_backoff_factor(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>().map_err(|e| e.to_string())? {
+        factor if factor >= 1.0 && factor.is_finite() => Ok(factor),
+        _ => Err("must be at least 1, lest the delay shrink with the failures".to_string()),
+    }
+}
+
+fn parse_backoff_jitter(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>().map_err(|e| e.to_string())? {
+        jitter if (0.0..1.0).contains(&jitter) => Ok(jitter),
+        _ => Err("must be in [0, 1), lest the delay fall to zero".to_string()),
+    }
+}
+
+fn parse_regex(s: &str) -> Result<Regex, String> {
+    Regex::new(s).map_err(|e| e.to_string())
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -30,6 +75,19 @@ async fn main() -> anyhow::Result<()> {
         #[clap(flatten)]
         admin: kubert::AdminArgs,
 
+        #[clap(flatten)]
+        server: kubert::ServerArgs,
+
+        #[clap(flatten)]
+        leader_election: leader::LeaderElectionArgs,
+
+        /// Serve the AppInstance validating admission webhook.
+        ///
+        /// The webhook is served over HTTPS on --server-addr using the certificate
+        /// and key passed with --server-tls-certs and --server-tls-key.
+        #[clap(long, env = "KUBIT_ADMISSION_WEBHOOK", default_value = "false")]
+        admission_webhook: bool,
+
         /// Kubectl image to use for the apply step of kubit.
         ///
         /// This MUST be greater than v1.27 of kubectl as we utilise the applyset
@@ -62,10 +120,18 @@ async fn main() -> anyhow::Result<()> {
         )]
         kubecfg_image: String,
 
+        /// Helm image to use within the render step of Helm charts
+        #[clap(
+            long,
+            env = "KUBIT_HELM_IMAGE",
+            default_value = engine::DEFAULT_HELM_IMAGE
+        )]
+        helm_image: String,
+
         #[clap(
             long,
             env = "KUBIT_CONTROLLER_IMAGE",
-           default_value = concat!("ghcr.io/kubecfg/kubit:v", env!("CARGO_PKG_VERSION"))
+            default_value = crate::controller::KUBIT_IMAGE
         )]
         kubit_image: String,
 
@@ -76,11 +142,87 @@ async fn main() -> anyhow::Result<()> {
         #[command(subcommand)]
         command: Option<Commands>,
 
-        #[clap(long, env = "KUBIT_WATCHED_NAMESPACE", default_value = None)]
-        watched_namespace: Option<String>,
+        /// Namespaces to watch, comma separated or repeated; all namespaces are watched by default.
+        #[clap(
+            long = "watched-namespace",
+            env = "KUBIT_WATCHED_NAMESPACE",
+            value_delimiter = ','
+        )]
+        watched_namespaces: Vec<String>,
+
+        /// Label selector of the namespaces to watch, in addition to `--watched-namespace`.
+        /// Namespaces are watched for as long as they match it.
+        #[clap(long, env = "KUBIT_NAMESPACE_SELECTOR")]
+        namespace_selector: Option<String>,
+
+        /// Read the instances from ConfigMaps instead of AppInstance resources, which doesn't
+        /// require the CRD. The namespaces must be set with `--watched-namespace` or
+        /// `--namespace-selector`.
+        #[clap(long, env = "KUBIT_CONFIG_MAP_MODE", default_value = "false")]
+        config_map_mode: bool,
+
+        /// Name of the ConfigMap defining the instance in ConfigMap mode, unless
+        /// `--config-map-selector` is set.
+        #[clap(long, env = "KUBIT_CONFIG_MAP_NAME", default_value = "app-instance")]
+        config_map_name: String,
+
+        /// Label selector of the ConfigMaps defining the instances in ConfigMap mode,
+        /// e.g. `kubit.kubecfg.dev/app-instance=true`, to reconcile more than one per namespace.
+        #[clap(long, env = "KUBIT_CONFIG_MAP_SELECTOR")]
+        config_map_selector: Option<String>,
+
+        /// Key of the ConfigMap data holding the AppInstance in ConfigMap mode.
+        #[clap(long, env = "KUBIT_CONFIG_MAP_KEY", default_value = controller::DEFAULT_CONFIG_MAP_KEY)]
+        config_map_key: String,
+
+        /// Path to a YAML file with the defaults for the `spec.jobTemplate` field of the AppInstances,
+        /// e.g. resources and security contexts of the jobs that install the packages.
+        #[clap(long, env = "KUBIT_DEFAULT_JOB_TEMPLATE")]
+        default_job_template: Option<PathBuf>,
+
+        /// Delay before retrying an AppInstance that failed for the first time, e.g. `5s`.
+        #[clap(long, env = "KUBIT_BACKOFF_INITIAL", default_value = "5s", value_parser = parse_backoff_initial)]
+        backoff_initial: Duration,
 
-        #[clap(long, default_value = "app-instance")]
-        config_map_name: Option<String>,
+        /// Upper bound of the delay before retrying an AppInstance that keeps failing, e.g. `5m`.
+        #[clap(long, env = "KUBIT_BACKOFF_MAX", default_value = "5m", value_parser = parse_duration)]
+        backoff_max: Duration,
+
+        /// Factor the retry delay is multiplied by after every consecutive failure.
+        #[clap(long, env = "KUBIT_BACKOFF_FACTOR", default_value = "2", value_parser = parse_backoff_factor)]
+        backoff_factor: f64,
+
+        /// Fraction of the retry delay that is randomly added or removed, e.g. 0.2 for ±20%.
+        #[clap(long, env = "KUBIT_BACKOFF_JITTER", default_value = "0.2", value_parser = parse_backoff_jitter)]
+        backoff_jitter: f64,
+
+        /// Number of trailing lines of each container log of the installation job kept in
+        /// `status.lastLogs`.
+        #[clap(long, env = "KUBIT_JOB_LOG_TAIL_LINES", default_value = "20")]
+        job_log_tail_lines: usize,
+
+        /// Number of installation jobs whose full logs are kept in the `kubit-logs-<name>` Secret.
+        #[clap(long, env = "KUBIT_JOB_LOG_HISTORY", default_value = "5")]
+        job_log_history: usize,
+
+        /// Regular expression matching sensitive values to mask in the captured job logs; if it
+        /// has capture groups only they are masked. Can be repeated.
+        #[clap(long = "redact-pattern", env = "KUBIT_REDACT_PATTERN", value_parser = parse_regex)]
+        redact_patterns: Vec<Regex>,
+
+        /// Grant the applier full access to the namespace of the instance (and to CRDs) when the
+        /// package doesn't declare the permissions it needs.
+        #[clap(long, env = "KUBIT_ALLOW_BROAD_APPLIER_ROLE", default_value = "false")]
+        allow_broad_applier_role: bool,
+
+        /// Require instances to set `spec.serviceAccountName`, instead of running their jobs as the
+        /// `kubit-applier` ServiceAccount set up by the controller.
+        #[clap(
+            long,
+            env = "KUBIT_REQUIRE_SERVICE_ACCOUNT_NAME",
+            default_value = "false"
+        )]
+        require_service_account_name: bool,
     }
 
     #[derive(Clone, Subcommand)]
@@ -90,9 +232,21 @@ async fn main() -> anyhow::Result<()> {
             // Optional directory to write CRDs into
             #[clap(
                 long,
-                help = "Optional directory to write CRDs into, otherwise write to stdout"
+                help = "Optional directory to write CRDs (and the webhook configuration) into, otherwise write to stdout"
             )]
             crd_dir: Option<PathBuf>,
+
+            /// Also generate the ValidatingWebhookConfiguration for the admission webhook
+            #[clap(long)]
+            webhook: bool,
+
+            /// Name of the Service fronting the admission webhook
+            #[clap(long, default_value = "kubit-webhook")]
+            webhook_service: String,
+
+            /// Namespace of the Service fronting the admission webhook
+            #[clap(long, default_value = "kubit")]
+            webhook_namespace: String,
         },
 
         /// Render scripts for various phases
@@ -139,40 +293,101 @@ async fn main() -> anyhow::Result<()> {
         log_format,
         client,
         admin,
+        server,
+        leader_election,
+        admission_webhook,
         kubecfg_image,
+        helm_image,
         kubit_image,
         apply_image_kubectl,
         render_image_kubectl,
         command,
         only_paused,
-        watched_namespace,
+        watched_namespaces,
+        namespace_selector,
+        config_map_mode,
         config_map_name,
+        config_map_selector,
+        config_map_key,
+        default_job_template,
+        backoff_initial,
+        backoff_max,
+        backoff_factor,
+        backoff_jitter,
+        job_log_tail_lines,
+        job_log_history,
+        redact_patterns,
+        allow_broad_applier_role,
+        require_service_account_name,
     } = Args::parse();
 
+    if backoff_initial > backoff_max {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--backoff-initial must not be greater than --backoff-max",
+            )
+            .exit();
+    }
+
+    let render_images = RenderImages {
+        kubecfg: kubecfg_image,
+        helm: helm_image,
+        kubit: kubit_image.clone(),
+        kubectl: render_image_kubectl.clone(),
+    };
+
     // Expand vector as more CRDs are created.
     let crds = vec![kubit::resources::AppInstance::crd()];
     match &command {
-        Some(Commands::Manifests { crd_dir }) => {
-            for crd in crds {
-                let mut out_writer: Box<dyn Write> = match crd_dir {
+        Some(Commands::Manifests {
+            crd_dir,
+            webhook,
+            webhook_service,
+            webhook_namespace,
+        }) => {
+            let open = |file_name: String| -> anyhow::Result<Box<dyn Write>> {
+                Ok(match crd_dir {
                     Some(dir) => {
-                        let crd_file = format!("{}_{}.yaml", crd.spec.group, crd.spec.names.plural);
-                        let file = File::create(dir.join(crd_file)).map_err(|e| {
-                            anyhow::anyhow!("Could not open AppInstances CRD file: {e}")
+                        let file = File::create(dir.join(&file_name)).map_err(|e| {
+                            anyhow::anyhow!("Could not open manifest file {file_name}: {e}")
                         })?;
 
                         Box::new(file)
                     }
                     None => Box::new(stdout()),
-                };
+                })
+            };
+
+            for crd in crds {
+                let mut out_writer =
+                    open(format!("{}_{}.yaml", crd.spec.group, crd.spec.names.plural))?;
                 // The YAML delimiter is added in the event we have multiple documents.
                 writeln!(out_writer, "---")?;
                 serde_yaml::to_writer(out_writer, &crd)?;
             }
+
+            if *webhook {
+                let mut out_writer = open(
+                    "admissionregistration.k8s.io_validatingwebhookconfigurations.yaml".into(),
+                )?;
+                writeln!(out_writer, "---")?;
+                serde_yaml::to_writer(
+                    out_writer,
+                    &webhook::validating_webhook_configuration(webhook_service, webhook_namespace),
+                )?;
+            }
         }
         Some(Commands::Metadata { metadata }) => metadata::run(metadata).await?,
         Some(Commands::Local { local }) => local::run(local, &client.impersonate_user).await?,
-        Some(Commands::Helper { helper }) => helpers::run(helper).await?,
+        Some(Commands::Helper { helper }) => {
+            // Helpers may write their output to stdout, so they log to stderr.
+            tracing_subscriber::registry()
+                .with(log_level)
+                .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+                .try_init()?;
+            helpers::run(helper).await?
+        }
         Some(Commands::Scripts {
             app_instance,
             script,
@@ -187,7 +402,7 @@ async fn main() -> anyhow::Result<()> {
                         &app_instance,
                         false,
                         *skip_auth,
-                        kubecfg_image,
+                        &render_images,
                         &mut output,
                     )
                     .await?
@@ -198,7 +413,15 @@ async fn main() -> anyhow::Result<()> {
             }
         }
         None => {
-            let prom = prometheus_client::registry::Registry::default();
+            let default_job_template: JobTemplate = match default_job_template {
+                Some(path) => serde_yaml::from_reader(File::open(&path).map_err(|e| {
+                    anyhow::anyhow!("Could not open job template file {}: {e}", path.display())
+                })?)?,
+                None => JobTemplate::default(),
+            };
+
+            let mut prom = prometheus_client::registry::Registry::default();
+            let metrics = metrics::Metrics::register(prom.sub_registry_with_prefix("kubit"));
 
             let admin = kubert::admin::Builder::from(admin).with_prometheus(prom);
 
@@ -206,22 +429,71 @@ async fn main() -> anyhow::Result<()> {
                 .with_log(log_level, log_format)
                 .with_admin(admin)
                 .with_client(client)
+                .with_optional_server(admission_webhook.then_some(server))
                 .build()
                 .await?;
 
+            // Standby replicas keep serving the admission webhook.
+            let webhook_client = rt.client();
+            let rt =
+                rt.spawn_server(|| webhook::service(webhook_client, require_service_account_name));
+
+            let candidate = if leader_election.leader_election {
+                Some(leader::candidate(&rt, leader_election).await?)
+            } else {
+                None
+            };
+            let shutdown = rt.shutdown_handle();
+
             let controller = controller::run(
                 rt.client(),
-                kubecfg_image,
+                render_images,
                 kubit_image,
                 apply_image_kubectl,
                 render_image_kubectl,
                 only_paused,
-                config_map_name,
-                watched_namespace,
+                config_map_mode.then_some(controller::ConfigMapSource {
+                    selector: config_map_selector,
+                    name: config_map_name,
+                    key: config_map_key,
+                }),
+                watched_namespaces,
+                namespace_selector,
+                default_job_template,
+                BackoffConfig {
+                    initial: backoff_initial,
+                    max: backoff_max,
+                    factor: backoff_factor,
+                    jitter: backoff_jitter,
+                },
+                metrics,
+                LogsConfig {
+                    tail_lines: job_log_tail_lines,
+                    history: job_log_history,
+                },
+                Redactor::new(redact_patterns),
+                allow_broad_applier_role,
+                require_service_account_name,
             );
 
+            // Standby replicas wait for the lease while the runtime runs, so that they answer
+            // the liveness and readiness probes and handle the termination signals.
+            let controller = async move {
+                match candidate {
+                    Some(candidate) => {
+                        leader::run_when_elected(candidate.acquire(), shutdown, controller).await
+                    }
+                    None => {
+                        drop(shutdown);
+                        controller.await
+                    }
+                }
+            };
+
             // Both runtimes implements graceful shutdown, so poll until both are done
-            tokio::join!(controller, rt.run()).1?;
+            let (controller, rt) = tokio::join!(controller, rt.run());
+            rt?;
+            controller?;
         }
     }
 
@@ -0,0 +1,248 @@
+use std::{fs, io::Read, path::Path, sync::LazyLock};
+
+use flate2::read::GzDecoder;
+use oci_distribution::{
+    client::ImageLayer,
+    manifest::{IMAGE_LAYER_GZIP_MEDIA_TYPE, IMAGE_LAYER_MEDIA_TYPE},
+    secrets::RegistryAuth,
+};
+use regex::{Captures, Regex};
+
+use crate::{oci, Error, Result};
+
+/// Config media type of the artifacts pushed with `flux push artifact`.
+pub const FLUX_CONFIG_MEDIA_TYPE: &str = "application/vnd.cncf.flux.config.v1+json";
+
+/// Config media types of the artifacts pushed with `oras push`, which default to an empty config.
+pub const ORAS_CONFIG_MEDIA_TYPES: &[&str] = &[
+    "application/vnd.oci.empty.v1+json",
+    "application/vnd.unknown.config.v1+json",
+];
+
+const FLUX_CONTENT_MEDIA_TYPE: &str = "application/vnd.cncf.flux.content.v1.tar+gzip";
+
+const LAYER_MEDIA_TYPES: &[&str] = &[
+    FLUX_CONTENT_MEDIA_TYPE,
+    IMAGE_LAYER_MEDIA_TYPE,
+    IMAGE_LAYER_GZIP_MEDIA_TYPE,
+];
+
+/// Name of a file pushed as is by oras, rather than in a tarball.
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+/// Matches the `${name}` variables of a manifest; the name is the first group.
+static VARIABLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_-]*(?:\.[A-Za-z0-9_-]+)*)\}").expect("valid regex")
+});
+
+/// A manifest file found in a bundle.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ManifestFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Pulls a bundle of plain manifests and returns its manifest files, sorted by path.
+pub async fn fetch(image: &str, auth: &RegistryAuth) -> Result<Vec<ManifestFile>> {
+    let layers = oci::pull_layers(image, auth, LAYER_MEDIA_TYPES).await?;
+    let mut files = vec![];
+    for layer in &layers {
+        files.extend(extract(layer)?);
+    }
+    if files.is_empty() {
+        // Applying nothing would prune everything previously applied.
+        return Err(Error::EmptyManifestsBundle);
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Pulls a bundle and unpacks all its files into `dir`, e.g. for kustomize to build it.
+pub async fn unpack(image: &str, auth: &RegistryAuth, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    for layer in oci::pull_layers(image, auth, LAYER_MEDIA_TYPES).await? {
+        let data = decompress(&layer)?;
+        if is_tarball(&data) {
+            // Refuses entries escaping `dir`.
+            tar::Archive::new(&data[..]).unpack(dir)?;
+        } else if let Some(file_name) = Path::new(&title(&layer)).file_name() {
+            fs::write(dir.join(file_name), data)?;
+        }
+    }
+    Ok(())
+}
+
+fn decompress(layer: &ImageLayer) -> Result<Vec<u8>> {
+    if !layer.data.starts_with(&[0x1f, 0x8b]) {
+        return Ok(layer.data.clone());
+    }
+    let mut data = vec![];
+    GzDecoder::new(&layer.data[..]).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Tarballs have the "ustar" magic in their first header.
+fn is_tarball(data: &[u8]) -> bool {
+    data.get(257..262) == Some(b"ustar")
+}
+
+fn title(layer: &ImageLayer) -> String {
+    layer
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(TITLE_ANNOTATION))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Returns the manifest files of a layer, which is either a (possibly gzipped) tarball
+/// or a single file.
+fn extract(layer: &ImageLayer) -> Result<Vec<ManifestFile>> {
+    let data = decompress(layer)?;
+    if !is_tarball(&data) {
+        let path = title(layer);
+        return Ok(if is_manifest(&path) {
+            vec![manifest_file(path, data)?]
+        } else {
+            vec![]
+        });
+    }
+
+    let mut files = vec![];
+    for entry in tar::Archive::new(&data[..]).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().to_string();
+        if !is_manifest(&path) {
+            continue;
+        }
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        files.push(manifest_file(path, content)?);
+    }
+    Ok(files)
+}
+
+fn manifest_file(path: String, content: Vec<u8>) -> Result<ManifestFile> {
+    let content = String::from_utf8(content)
+        .map_err(|_| Error::InvalidManifestsBundle(format!("{path} is not UTF-8")))?;
+    Ok(ManifestFile { path, content })
+}
+
+/// Tells whether a file of a bundle holds resources to apply.
+///
+/// Kustomizations, which bundles built for Flux often carry, aren't resources.
+fn is_manifest(path: &str) -> bool {
+    let path = Path::new(path);
+    let extension = path.extension().and_then(|e| e.to_str());
+    let file_stem = path.file_stem().and_then(|s| s.to_str());
+    matches!(extension, Some("yaml" | "yml" | "json"))
+        && !matches!(file_stem, Some("kustomization" | "Kustomization"))
+}
+
+/// Replaces the `${name}` variables of a manifest with the scalar at `name` in `values`,
+/// where `name` is a dot separated path, e.g. `${image.tag}`.
+///
+/// Other variables are left as is, as manifests may legitimately contain shell variables.
+pub fn substitute(manifest: &str, values: &serde_json::Value) -> String {
+    VARIABLE
+        .replace_all(manifest, |caps: &Captures<'_>| {
+            let value = caps[1]
+                .split('.')
+                .try_fold(values, |value, key| value.get(key));
+            match value {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(v @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+                    v.to_string()
+                }
+                _ => caps[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Joins the manifest files of a bundle into a single multi-document YAML stream,
+/// substituting their variables.
+pub fn render(files: &[ManifestFile], values: &serde_json::Value) -> String {
+    files
+        .iter()
+        .map(|file| {
+            format!(
+                "---\n# Source: {}\n{}\n",
+                file.path,
+                substitute(file.content.trim_end(), values)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn extract_tarball() {
+        let data = tarball(&[
+            ("deploy/deployment.yaml", "kind: Deployment\n"),
+            ("deploy/kustomization.yaml", "resources: []\n"),
+            ("README.md", "# demo\n"),
+        ]);
+        let mut gzipped = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        std::io::Write::write_all(&mut gzipped, &data).unwrap();
+        let layer = ImageLayer::new(
+            gzipped.finish().unwrap(),
+            FLUX_CONTENT_MEDIA_TYPE.to_string(),
+            None,
+        );
+
+        assert_eq!(
+            extract(&layer).unwrap(),
+            vec![ManifestFile {
+                path: "deploy/deployment.yaml".to_string(),
+                content: "kind: Deployment\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn extract_single_file() {
+        let layer = ImageLayer::new(
+            b"kind: Service\n".to_vec(),
+            IMAGE_LAYER_MEDIA_TYPE.to_string(),
+            Some([(TITLE_ANNOTATION.to_string(), "service.yml".to_string())].into()),
+        );
+        assert_eq!(extract(&layer).unwrap()[0].path, "service.yml");
+    }
+
+    #[test]
+    fn substitute_values() {
+        let values = serde_json::json!({
+            "replicas": 3,
+            "image": { "tag": "v1.2.3" },
+            "resources": { "cpu": "100m" },
+        });
+        assert_eq!(
+            substitute(
+                "replicas: ${replicas}\nimage: demo:${image.tag}\nresources: ${resources}\nargs: [\"${HOME}\"]",
+                &values
+            ),
+            "replicas: 3\nimage: demo:v1.2.3\nresources: ${resources}\nargs: [\"${HOME}\"]"
+        );
+    }
+}
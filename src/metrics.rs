@@ -0,0 +1,324 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+
+use crate::resources::AppInstanceStatus;
+
+/// Conditions exported as a per-instance gauge.
+const EXPORTED_CONDITIONS: [&str; 2] = ["Ready", "Healthy"];
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct OutcomeLabels {
+    outcome: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct InstanceLabels {
+    namespace: String,
+    instance: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ConditionLabels {
+    namespace: String,
+    instance: String,
+    condition: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RegistryLabels {
+    registry: String,
+}
+
+type HistogramFamily<L> = Family<L, Histogram, fn() -> Histogram>;
+
+/// Metrics exported by the controller.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    reconciles: Family<OutcomeLabels, Counter>,
+    reconcile_duration: HistogramFamily<OutcomeLabels>,
+    reconciles_in_progress: Gauge,
+    queue_depth: Gauge,
+    /// Instances, as `namespace/name`, with a reconciliation scheduled by the controller.
+    scheduled: Arc<Mutex<HashSet<String>>>,
+    apply_job_duration: HistogramFamily<InstanceLabels>,
+    apply_job_failures: Family<InstanceLabels, Counter>,
+    registry_fetch_duration: HistogramFamily<RegistryLabels>,
+    registry_fetch_errors: Family<RegistryLabels, Counter>,
+    conditions: Family<ConditionLabels, Gauge>,
+}
+
+impl Metrics {
+    /// Creates the metrics and registers them in the given registry.
+    pub fn register(registry: &mut Registry) -> Self {
+        let metrics = Self {
+            reconciles: Family::default(),
+            // From 10ms to ~40s
+            reconcile_duration: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.01, 2.0, 13))
+            }),
+            reconciles_in_progress: Gauge::default(),
+            queue_depth: Gauge::default(),
+            scheduled: Default::default(),
+            // From 5s to ~40m
+            apply_job_duration: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(5.0, 2.0, 10))
+            }),
+            apply_job_failures: Family::default(),
+            // From 50ms to ~50s
+            registry_fetch_duration: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.05, 2.0, 11))
+            }),
+            registry_fetch_errors: Family::default(),
+            conditions: Family::default(),
+        };
+
+        registry.register(
+            "reconciles",
+            "Number of reconciliations, by outcome",
+            metrics.reconciles.clone(),
+        );
+        registry.register(
+            "reconcile_duration_seconds",
+            "Duration of the reconciliations, by outcome",
+            metrics.reconcile_duration.clone(),
+        );
+        registry.register(
+            "reconciles_in_progress",
+            "Number of reconciliations in progress",
+            metrics.reconciles_in_progress.clone(),
+        );
+        registry.register(
+            "reconcile_queue_depth",
+            "Number of instances with a reconciliation scheduled by the controller, i.e. a resync or a retry",
+            metrics.queue_depth.clone(),
+        );
+        registry.register(
+            "apply_job_duration_seconds",
+            "Duration of the jobs applying the packages, by instance",
+            metrics.apply_job_duration.clone(),
+        );
+        registry.register(
+            "apply_job_failures",
+            "Number of failed jobs applying the packages, by instance",
+            metrics.apply_job_failures.clone(),
+        );
+        registry.register(
+            "registry_fetch_duration_seconds",
+            "Latency of fetching the package configs from the OCI registries",
+            metrics.registry_fetch_duration.clone(),
+        );
+        registry.register(
+            "registry_fetch_errors",
+            "Number of errors fetching the package configs from the OCI registries",
+            metrics.registry_fetch_errors.clone(),
+        );
+        registry.register(
+            "instance_condition",
+            "Whether the Ready and Healthy conditions of the instances are True (1) or not (0)",
+            metrics.conditions.clone(),
+        );
+
+        metrics
+    }
+
+    /// Tracks a reconciliation until the returned guard is dropped.
+    ///
+    /// The reconciliation scheduled for the instance, if any, is the one starting (or is
+    /// superseded by it).
+    pub fn reconcile_started(&self, namespace: &str, instance: &str) -> InProgressGuard {
+        self.unschedule(namespace, instance);
+        self.reconciles_in_progress.inc();
+        InProgressGuard(self.reconciles_in_progress.clone())
+    }
+
+    /// Records whether the controller scheduled another reconciliation of the instance, as
+    /// opposed to waiting for a change.
+    ///
+    /// The queue of the controller runtime isn't observable, so the queue depth is the number
+    /// of instances with a reconciliation scheduled that hasn't started yet.
+    pub fn reconcile_scheduled(&self, namespace: &str, instance: &str, scheduled: bool) {
+        if !scheduled {
+            return self.unschedule(namespace, instance);
+        }
+        let mut all = self.scheduled.lock().expect("poisoned lock");
+        all.insert(format!("{namespace}/{instance}"));
+        self.queue_depth.set(all.len() as i64);
+    }
+
+    fn unschedule(&self, namespace: &str, instance: &str) {
+        let mut all = self.scheduled.lock().expect("poisoned lock");
+        all.remove(&format!("{namespace}/{instance}"));
+        self.queue_depth.set(all.len() as i64);
+    }
+
+    pub fn reconcile_finished(&self, success: bool, duration: Duration) {
+        let labels = OutcomeLabels {
+            outcome: if success { "success" } else { "error" },
+        };
+        self.reconciles.get_or_create(&labels).inc();
+        self.reconcile_duration
+            .get_or_create(&labels)
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn apply_job_finished(
+        &self,
+        namespace: &str,
+        instance: &str,
+        success: bool,
+        duration: Option<Duration>,
+    ) {
+        let labels = InstanceLabels {
+            namespace: namespace.to_string(),
+            instance: instance.to_string(),
+        };
+        if let Some(duration) = duration {
+            self.apply_job_duration
+                .get_or_create(&labels)
+                .observe(duration.as_secs_f64());
+        }
+        if !success {
+            self.apply_job_failures.get_or_create(&labels).inc();
+        }
+    }
+
+    pub fn registry_fetched(&self, registry: &str, success: bool, duration: Duration) {
+        let labels = RegistryLabels {
+            registry: registry.to_string(),
+        };
+        self.registry_fetch_duration
+            .get_or_create(&labels)
+            .observe(duration.as_secs_f64());
+        if !success {
+            self.registry_fetch_errors.get_or_create(&labels).inc();
+        }
+    }
+
+    /// Exports the conditions found in the status of an instance.
+    pub fn observe_status(&self, namespace: &str, instance: &str, status: &AppInstanceStatus) {
+        for condition in EXPORTED_CONDITIONS {
+            let Some(found) = status.conditions.iter().find(|c| c.type_ == condition) else {
+                continue;
+            };
+            let value = i64::from(found.status == "True");
+            self.conditions
+                .get_or_create(&condition_labels(namespace, instance, condition))
+                .set(value);
+        }
+    }
+
+    /// Stops exporting the per-instance series of a deleted instance.
+    pub fn forget_instance(&self, namespace: &str, instance: &str) {
+        for condition in EXPORTED_CONDITIONS {
+            self.conditions
+                .remove(&condition_labels(namespace, instance, condition));
+        }
+        let labels = InstanceLabels {
+            namespace: namespace.to_string(),
+            instance: instance.to_string(),
+        };
+        self.apply_job_duration.remove(&labels);
+        self.apply_job_failures.remove(&labels);
+        self.unschedule(namespace, instance);
+    }
+}
+
+fn condition_labels(namespace: &str, instance: &str, condition: &'static str) -> ConditionLabels {
+    ConditionLabels {
+        namespace: namespace.to_string(),
+        instance: instance.to_string(),
+        condition,
+    }
+}
+
+/// Decrements the reconciliations in progress when a reconciliation is done, however it ends.
+pub struct InProgressGuard(Gauge);
+
+impl Drop for InProgressGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::AppInstanceCondition;
+    use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+    use prometheus_client::encoding::text::encode;
+
+    fn condition(type_: &str, status: &str) -> AppInstanceCondition {
+        AppInstanceCondition {
+            last_transition_time: Time(Utc::now()),
+            message: String::new(),
+            observed_generation: None,
+            reason: "Test".to_string(),
+            status: status.to_string(),
+            type_: type_.to_string(),
+        }
+    }
+
+    #[test]
+    fn condition_gauges() {
+        let mut registry = Registry::with_prefix("kubit");
+        let metrics = Metrics::register(&mut registry);
+
+        let status = AppInstanceStatus {
+            conditions: vec![condition("Ready", "True"), condition("Healthy", "False")],
+            ..Default::default()
+        };
+        metrics.observe_status("ns", "foo", &status);
+
+        let mut out = String::new();
+        encode(&mut out, &registry).unwrap();
+        assert!(out.contains(
+            r#"kubit_instance_condition{namespace="ns",instance="foo",condition="Ready"} 1"#
+        ));
+        assert!(out.contains(
+            r#"kubit_instance_condition{namespace="ns",instance="foo",condition="Healthy"} 0"#
+        ));
+
+        metrics.apply_job_finished("ns", "foo", false, Some(Duration::from_secs(10)));
+        metrics.forget_instance("ns", "foo");
+        let mut out = String::new();
+        encode(&mut out, &registry).unwrap();
+        assert!(!out.contains(r#"instance="foo""#));
+    }
+
+    #[test]
+    fn queue_depth() {
+        let mut registry = Registry::with_prefix("kubit");
+        let metrics = Metrics::register(&mut registry);
+        let queue_depth = |metrics: &Metrics| metrics.queue_depth.get();
+
+        metrics.reconcile_scheduled("ns", "foo", true);
+        metrics.reconcile_scheduled("ns", "bar", true);
+        metrics.reconcile_scheduled("ns", "foo", true);
+        assert_eq!(queue_depth(&metrics), 2);
+
+        let guard = metrics.reconcile_started("ns", "foo");
+        assert_eq!(queue_depth(&metrics), 1);
+        assert_eq!(metrics.reconciles_in_progress.get(), 1);
+        drop(guard);
+        metrics.reconcile_scheduled("ns", "foo", false);
+        assert_eq!(queue_depth(&metrics), 1);
+        assert_eq!(metrics.reconciles_in_progress.get(), 0);
+
+        metrics.forget_instance("ns", "bar");
+        assert_eq!(queue_depth(&metrics), 0);
+    }
+}
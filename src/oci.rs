@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
-use oci_distribution::{manifest::OciManifest, secrets::RegistryAuth, Client, Reference};
+use oci_distribution::{
+    client::ImageLayer, manifest::OciManifest, secrets::RegistryAuth, Client, Reference,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::resources::AppInstance;
@@ -44,8 +46,14 @@ pub enum Error {
     #[error("Error serializing JSON schema: {0}")]
     SerializeJSONSchema(serde_json::Error),
 
+    #[error("Error decoding permissions under kubit.kubecfg.dev/v1alpha1: {0}")]
+    DecodePermissions(serde_json::Error),
+
     #[error("Error serializing image list: {0}")]
     SerializeImageList(serde_json::Error),
+
+    #[error("Invalid JSON schema in package metadata: {0}")]
+    InvalidJSONSchema(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -53,18 +61,80 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageConfig {
+    /// Only set in kubecfg packages: Helm chart configs have no entrypoint.
+    #[serde(default)]
     entrypoint: String,
     #[serde(default)]
     metadata: HashMap<String, serde_json::Value>,
+    /// Digest of the manifest the config was fetched from; not part of the config blob itself.
+    #[serde(skip)]
+    digest: String,
+    /// Media type of the config blob, as declared by the package manifest.
+    #[serde(skip)]
+    media_type: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct KubecfgPackageMetadata {
-    version: String,
+    pub version: String,
+}
+
+/// Permissions the applier needs to install a package, as declared under `permissions`
+/// in the `kubit.kubecfg.dev/v1alpha1` metadata of the package.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackagePermissions {
+    /// Resources installed in the namespace of the instance.
+    #[serde(default)]
+    pub namespaced: Vec<PermissionRule>,
+    /// Cluster-scoped resources, e.g. CRDs.
+    #[serde(default)]
+    pub cluster: Vec<PermissionRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionRule {
+    /// API groups of the resources; the core group is `""`.
+    pub api_groups: Vec<String>,
+    pub resources: Vec<String>,
+    /// Defaults to the verbs needed to apply and prune the resources.
+    #[serde(default)]
+    pub verbs: Option<Vec<String>>,
 }
 
 impl PackageConfig {
+    /// Returns the digest of the package manifest, as resolved when fetching the config.
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+
+    /// Returns the media type of the config, e.g. telling Helm charts apart from kubecfg packages.
+    pub fn media_type(&self) -> &str {
+        &self.media_type
+    }
+
+    #[cfg(test)]
+    pub fn with_media_type(self, media_type: &str) -> Self {
+        PackageConfig {
+            media_type: media_type.to_string(),
+            ..self
+        }
+    }
+
+    /// Returns the package image reference pinned to the resolved manifest digest,
+    /// so that later pulls get exactly the same package even if the tag moves.
+    pub fn pinned_image(&self, image: &str) -> Result<String> {
+        let reference: Reference = image.parse()?;
+        Ok(format!(
+            "{}/{}@{}",
+            reference.registry(),
+            reference.repository(),
+            self.digest
+        ))
+    }
+
     pub fn kubecfg_package_metadata(&self) -> Result<KubecfgPackageMetadata> {
         serde_json::from_value(
             self.metadata
@@ -80,15 +150,60 @@ impl PackageConfig {
         Ok(format!("{}:{kubecfg_version}", kubecfg_image))
     }
 
+    fn schema_value(&self) -> Result<&serde_json::Value> {
+        self.metadata
+            .get(KUBIT_KEY)
+            .ok_or(Error::MissingMetadataKeyKubit)?
+            .get("schema")
+            .ok_or(Error::MissingMetadataKeyKubitSchema)
+    }
+
     pub fn schema(&self) -> Result<String> {
-        serde_json::to_string_pretty(
-            self.metadata
-                .get(KUBIT_KEY)
-                .ok_or(Error::MissingMetadataKeyKubit)?
-                .get("schema")
-                .ok_or(Error::MissingMetadataKeyKubitSchema)?,
-        )
-        .map_err(Error::SerializeJSONSchema)
+        serde_json::to_string_pretty(self.schema_value()?).map_err(Error::SerializeJSONSchema)
+    }
+
+    /// Validates a package `spec` against the JSON schema published by the package.
+    ///
+    /// Returns one message per violation, prefixed with the JSON pointer of the offending field.
+    /// Packages that don't publish a schema accept any spec.
+    pub fn validate_spec(&self, spec: &serde_json::Value) -> Result<Vec<String>> {
+        let schema = match self.schema_value() {
+            Ok(schema) => schema,
+            Err(Error::MissingMetadataKeyKubit | Error::MissingMetadataKeyKubitSchema) => {
+                return Ok(vec![])
+            }
+            Err(e) => return Err(e),
+        };
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| Error::InvalidJSONSchema(e.to_string()))?;
+
+        Ok(validator
+            .iter_errors(spec)
+            .map(|e| {
+                let path = e.instance_path().to_string();
+                let path = if path.is_empty() { "/" } else { &path };
+                format!("{path}: {e}")
+            })
+            .collect())
+    }
+
+    /// Returns the engine declared by the package, if any.
+    pub fn engine(&self) -> Option<&str> {
+        self.metadata
+            .get(KUBIT_KEY)
+            .and_then(|kubit| kubit.get("engine"))
+            .and_then(|engine| engine.as_str())
+    }
+
+    /// Returns the permissions declared by the package, if any.
+    pub fn permissions(&self) -> Result<Option<PackagePermissions>> {
+        self.metadata
+            .get(KUBIT_KEY)
+            .and_then(|kubit| kubit.get("permissions"))
+            .map(|permissions| {
+                serde_json::from_value(permissions.clone()).map_err(Error::DecodePermissions)
+            })
+            .transpose()
     }
 
     pub fn images(&self) -> Result<Vec<String>> {
@@ -116,7 +231,7 @@ pub async fn fetch_package_config(
     };
     let mut client = Client::new(client_config);
     let reference: Reference = image.parse()?;
-    let (manifest, _) = client.pull_manifest(&reference, auth).await?;
+    let (manifest, digest) = client.pull_manifest(&reference, auth).await?;
 
     let manifest = match manifest {
         OciManifest::Image(manifest) => manifest,
@@ -128,7 +243,139 @@ pub async fn fetch_package_config(
         .pull_blob(&reference, &manifest.config.digest, &mut buf)
         .await?;
 
-    let config = serde_json::from_slice(&buf).map_err(Error::DecodePackageConfig)?;
+    let config = PackageConfig {
+        digest,
+        media_type: manifest.config.media_type,
+        ..serde_json::from_slice(&buf).map_err(Error::DecodePackageConfig)?
+    };
 
     Ok(config)
 }
+
+/// Pulls the layers of the package image, which must all be of one of `media_types`.
+pub async fn pull_layers(
+    image: &str,
+    auth: &RegistryAuth,
+    media_types: &[&str],
+) -> Result<Vec<ImageLayer>> {
+    let client_config = oci_distribution::client::ClientConfig {
+        protocol: oci_distribution::client::ClientProtocol::Https,
+        ..Default::default()
+    };
+    let mut client = Client::new(client_config);
+    let reference: Reference = image.parse()?;
+    let image = client.pull(&reference, auth, media_types.to_vec()).await?;
+    Ok(image.layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_config(metadata: serde_json::Value) -> PackageConfig {
+        serde_json::from_value(serde_json::json!({
+            "entrypoint": "main.jsonnet",
+            "metadata": metadata,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn decode_helm_chart_config() {
+        let config: PackageConfig = serde_json::from_value(serde_json::json!({
+            "name": "demo",
+            "version": "1.2.3",
+            "apiVersion": "v2",
+        }))
+        .unwrap();
+        assert!(config.metadata.is_empty());
+    }
+
+    #[test]
+    fn validate_spec() {
+        let config = package_config(serde_json::json!({
+            KUBIT_KEY: {
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "replicas": { "type": "integer" },
+                        "image": {
+                            "type": "object",
+                            "properties": { "tag": { "type": "string" } },
+                        },
+                    },
+                    "additionalProperties": false,
+                },
+            },
+        }));
+
+        let errors = config
+            .validate_spec(&serde_json::json!({"replicas": 3, "image": {"tag": "v1"}}))
+            .unwrap();
+        assert!(errors.is_empty(), "{errors:?}");
+
+        let errors = config
+            .validate_spec(&serde_json::json!({"replicas": "3", "image": {"tag": 1}}))
+            .unwrap();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors.iter().any(|e| e.starts_with("/replicas: ")));
+        assert!(errors.iter().any(|e| e.starts_with("/image/tag: ")));
+
+        let errors = config
+            .validate_spec(&serde_json::json!({"replicsa": 3}))
+            .unwrap();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].starts_with("/: "));
+    }
+
+    #[test]
+    fn pinned_image() {
+        let config = PackageConfig {
+            digest: "sha256:0123".to_string(),
+            ..package_config(serde_json::json!({}))
+        };
+        assert_eq!(
+            config
+                .pinned_image("ghcr.io/kubecfg/kubit/package-demo:v1")
+                .unwrap(),
+            "ghcr.io/kubecfg/kubit/package-demo@sha256:0123"
+        );
+    }
+
+    #[test]
+    fn validate_spec_without_schema() {
+        let config = package_config(serde_json::json!({}));
+        let errors = config
+            .validate_spec(&serde_json::json!({"anything": "goes"}))
+            .unwrap();
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn permissions() {
+        let config = package_config(serde_json::json!({
+            KUBIT_KEY: {
+                "permissions": {
+                    "namespaced": [
+                        { "apiGroups": ["apps"], "resources": ["deployments"] },
+                        { "apiGroups": [""], "resources": ["configmaps"], "verbs": ["get"] },
+                    ],
+                },
+            },
+        }));
+        let permissions = config.permissions().unwrap().unwrap();
+        assert_eq!(permissions.namespaced.len(), 2);
+        assert_eq!(
+            permissions.namespaced[1].verbs,
+            Some(vec!["get".to_string()])
+        );
+        assert!(permissions.cluster.is_empty());
+
+        let config = package_config(serde_json::json!({ KUBIT_KEY: { "schema": {} } }));
+        assert_eq!(config.permissions().unwrap(), None);
+
+        let config =
+            package_config(serde_json::json!({ KUBIT_KEY: { "permissions": { "cluster": 1 } } }));
+        assert!(config.permissions().is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use std::{ops::Range, sync::LazyLock};
+
+use base64::{engine::general_purpose, Engine as _};
+use regex::Regex;
+
+const MASK: &str = "[REDACTED]";
+
+/// Matches base64 values of fields, like the ones of the `data` of a Secret rendered as
+/// YAML or JSON; the value is the second group.
+static BASE64_FIELD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"("?[-._a-zA-Z0-9]+"?\s*:\s*"?)([A-Za-z0-9+/]{16,}={0,2})(?:["',}\s]|$)"#)
+        .expect("valid regex")
+});
+
+/// Masks sensitive values in logs.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    values: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Masks the matches of the given patterns, or only their capture groups if they have any.
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        Self {
+            values: vec![],
+            patterns,
+        }
+    }
+
+    /// Also masks the given values wherever they appear.
+    pub fn with_values(mut self, values: impl IntoIterator<Item = String>) -> Self {
+        self.values
+            .extend(values.into_iter().filter(|value| !value.is_empty()));
+        self
+    }
+
+    pub fn redact(&self, log: &str) -> String {
+        let mut ranges: Vec<Range<usize>> = vec![];
+
+        for value in &self.values {
+            ranges.extend(log.match_indices(value).map(|(i, m)| i..i + m.len()));
+        }
+
+        for captures in BASE64_FIELD.captures_iter(log) {
+            let value = captures.get(2).expect("group is not optional");
+            if value.len() % 4 == 0 && general_purpose::STANDARD.decode(value.as_str()).is_ok() {
+                ranges.push(value.range());
+            }
+        }
+
+        for pattern in &self.patterns {
+            for captures in pattern.captures_iter(log) {
+                if captures.len() == 1 {
+                    ranges.extend(captures.get(0).map(|m| m.range()));
+                } else {
+                    ranges.extend(captures.iter().skip(1).flatten().map(|m| m.range()));
+                }
+            }
+        }
+
+        ranges.sort_by_key(|range| range.start);
+        let mut redacted = String::with_capacity(log.len());
+        let mut pos = 0;
+        for range in ranges {
+            if range.start >= pos {
+                redacted.push_str(&log[pos..range.start]);
+                redacted.push_str(MASK);
+            }
+            pos = pos.max(range.end);
+        }
+        redacted.push_str(&log[pos..]);
+        redacted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_values_and_patterns() {
+        let redactor = Redactor::new(vec![Regex::new(r"token=(\S+)").unwrap()])
+            .with_values(["hunter12".to_string(), String::new()]);
+
+        assert_eq!(
+            redactor.redact("login foo:hunter12 failed, token=abc def"),
+            "login foo:[REDACTED] failed, token=[REDACTED] def"
+        );
+    }
+
+    #[test]
+    fn redact_base64_fields() {
+        let redactor = Redactor::default();
+        let log = r#"data:
+  password: aHVudGVyMTJodW50ZXIxMg==
+  short: Zm9v
+{"token":"c2VjcmV0c2VjcmV0c2VjcmV0","image":"ghcr.io/kubecfg/demo:v0.1.0"}"#;
+
+        assert_eq!(
+            redactor.redact(log),
+            r#"data:
+  password: [REDACTED]
+  short: Zm9v
+{"token":"[REDACTED]","image":"ghcr.io/kubecfg/demo:v0.1.0"}"#
+        );
+    }
+
+    #[test]
+    fn overlapping_matches() {
+        let redactor = Redactor::default().with_values(["secret".to_string(), "cre".to_string()]);
+        assert_eq!(redactor.redact("a secret b"), "a [REDACTED] b");
+    }
+}
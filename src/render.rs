@@ -1,4 +1,9 @@
-use crate::{metadata, resources::AppInstance, scripting::Script, Error, Result};
+use crate::{
+    engine::{RenderEngine, RenderImages},
+    metadata,
+    resources::AppInstance,
+    Result,
+};
 use home::home_dir;
 use std::env;
 
@@ -10,50 +15,32 @@ pub async fn emit_script<W>(
     app_instance: &AppInstance,
     docker: bool,
     skip_auth: bool,
-    kubecfg_image: String,
+    render_images: &RenderImages,
     w: &mut W,
 ) -> Result<()>
 where
     W: std::io::Write,
 {
+    let engine = RenderEngine::for_local_package(app_instance, skip_auth).await;
+
     let tmp = tempfile::Builder::new().suffix(".json").tempfile()?;
     let (mut file, path) = tmp.keep()?;
-    serde_json::to_writer(&mut file, &app_instance).map_err(Error::RenderOverlay)?;
+    engine.write_overlay(app_instance, &mut file)?;
 
-    let script = script(
-        app_instance,
-        &path.to_string_lossy(),
-        Some("/tmp/manifests"),
-        docker,
-        skip_auth,
-        kubecfg_image,
-    )
-    .await?;
+    let script = engine
+        .script(
+            app_instance,
+            &path.to_string_lossy(),
+            Some("/tmp/manifests"),
+            docker,
+            skip_auth,
+            render_images,
+        )
+        .await?;
     writeln!(w, "{script}")?;
     Ok(())
 }
 
-/// Generates shell script that will render the manifest
-pub async fn script(
-    app_instance: &AppInstance,
-    overlay_file_name: &str,
-    output_dir: Option<&str>,
-    docker: bool,
-    skip_auth: bool,
-    kubecfg_image: String,
-) -> Result<Script> {
-    let tokens = emit_commandline(
-        app_instance,
-        overlay_file_name,
-        output_dir,
-        docker,
-        skip_auth,
-        kubecfg_image,
-    )
-    .await;
-    Ok(Script::from_vec(tokens))
-}
-
 pub async fn emit_commandline(
     app_instance: &AppInstance,
     overlay_file: &str,
@@ -191,13 +178,20 @@ pub async fn emit_commandline(
     cli
 }
 
-pub fn emit_fetch_app_instance_commandline(ns: &str, name: &str, output_file: &str) -> Vec<String> {
+pub fn emit_fetch_app_instance_commandline(
+    ns: &str,
+    name: &str,
+    engine: RenderEngine,
+    output_file: &str,
+) -> Vec<String> {
     [
         "kubit",
         "helper",
         "fetch-app-instance",
         "--namespace",
         ns,
+        "--engine",
+        &engine.to_string(),
         "--output",
         output_file,
         name,
@@ -210,6 +204,8 @@ pub fn emit_fetch_app_instance_commandline(ns: &str, name: &str, output_file: &s
 pub fn emit_fetch_appinstance_from_config_map_commandline(
     ns: &str,
     name: &str,
+    key: &str,
+    engine: RenderEngine,
     output_file: &str,
 ) -> Vec<String> {
     [
@@ -218,6 +214,10 @@ pub fn emit_fetch_appinstance_from_config_map_commandline(
         "fetch-app-instance-from-config-map",
         "--namespace",
         ns,
+        "--engine",
+        &engine.to_string(),
+        "--key",
+        key,
         "--output",
         output_file,
         name,
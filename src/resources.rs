@@ -1,7 +1,13 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use k8s_openapi::{
-    api::core::v1::{ConfigMap, LocalObjectReference},
+    api::core::v1::{
+        ConfigMap, LocalObjectReference, PodSecurityContext, ResourceRequirements, SecurityContext,
+        Toleration,
+    },
     apimachinery::pkg::apis::meta::v1::Time,
 };
 use kube::{CustomResource, ResourceExt};
@@ -33,6 +39,136 @@ pub struct AppInstanceSpec {
     /// You can use this if you need to do some manual changes (either with kubectl directly or with kubit CLI)
     #[serde(default)]
     pub pause: bool,
+
+    /// Overrides for the pods of the jobs that install and remove the package.
+    /// Unset fields fall back to the defaults configured in the controller.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_template: Option<JobTemplate>,
+
+    /// If set, the package is periodically applied again, e.g. every `1h`, even if the spec
+    /// didn't change. It must be at least `1m`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resync_interval: Option<String>,
+
+    /// If set, the controller periodically checks whether the installed resources drifted
+    /// from what the package would apply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drift_detection: Option<DriftDetection>,
+
+    /// Name of an existing ServiceAccount, in the namespace of the instance, that the jobs run as.
+    /// When set, the controller doesn't set up the `kubit-applier` ServiceAccount nor its roles:
+    /// granting the account the permissions needed to install the package is up to its owner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account_name: Option<String>,
+
+    /// Installs the package in another cluster than the one of the instance, whose status stays
+    /// in this cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_cluster: Option<TargetCluster>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct JobTemplate {
+    /// Compute resources of every container of the jobs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceRequirements>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_selector: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerations: Option<Vec<Toleration>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority_class_name: Option<String>,
+
+    /// Security context of the pods of the jobs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_context: Option<PodSecurityContext>,
+
+    /// Security context of every container of the jobs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_security_context: Option<SecurityContext>,
+
+    /// How long the pods of the jobs may run before being terminated. Defaults to 180 seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_deadline_seconds: Option<i64>,
+
+    /// How many times the jobs are retried. Defaults to 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff_limit: Option<i32>,
+}
+
+impl JobTemplate {
+    /// Returns this template with the fields that are not set taken from `defaults`.
+    pub fn or(&self, defaults: &JobTemplate) -> JobTemplate {
+        let this = self.clone();
+        let defaults = defaults.clone();
+        JobTemplate {
+            resources: this.resources.or(defaults.resources),
+            node_selector: this.node_selector.or(defaults.node_selector),
+            tolerations: this.tolerations.or(defaults.tolerations),
+            priority_class_name: this.priority_class_name.or(defaults.priority_class_name),
+            security_context: this.security_context.or(defaults.security_context),
+            container_security_context: this
+                .container_security_context
+                .or(defaults.container_security_context),
+            active_deadline_seconds: this
+                .active_deadline_seconds
+                .or(defaults.active_deadline_seconds),
+            backoff_limit: this.backoff_limit.or(defaults.backoff_limit),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DriftDetection {
+    /// How often to check for drift, e.g. `10m` or `1h30m`. It must be at least `1m`.
+    pub interval: String,
+
+    /// If true, the package is re-applied as soon as a drift is detected.
+    #[serde(default)]
+    pub auto_correct: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetCluster {
+    /// Secret, in the namespace of the instance, holding the kubeconfig used to install the package.
+    pub kubeconfig_secret_ref: KubeconfigSecretRef,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KubeconfigSecretRef {
+    pub name: String,
+
+    /// Key of the kubeconfig in the Secret, `value` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl KubeconfigSecretRef {
+    pub fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or("value")
+    }
+}
+
+impl DriftDetection {
+    pub fn interval(&self) -> crate::Result<std::time::Duration> {
+        crate::duration::parse_interval(&self.interval)
+    }
+}
+
+impl AppInstanceSpec {
+    pub fn resync_interval(&self) -> crate::Result<Option<std::time::Duration>> {
+        self.resync_interval
+            .as_deref()
+            .map(crate::duration::parse_interval)
+            .transpose()
+    }
 }
 
 impl AppInstance {
@@ -73,10 +209,104 @@ fn preserve_arbitrary(_gen: &mut schemars::gen::SchemaGenerator) -> Schema {
 #[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AppInstanceStatus {
+    /// The last lines of the logs of each container of the last installation job.
     pub last_logs: Option<HashMap<String, String>>,
+
+    /// Where the full logs of the last installation job are stored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_logs: Option<LogsReference>,
+
     #[serde(default)]
     #[serde(skip_serializing_if = "<[_]>::is_empty")]
     pub conditions: Vec<AppInstanceCondition>,
+
+    /// Digest of the package manifest that was resolved from `spec.package.image`
+    /// and applied by the last successful installation job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package_digest: Option<String>,
+
+    /// Version of kubecfg used to render the package in the last successful installation job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kubecfg_version: Option<String>,
+
+    /// The `metadata.generation` of the AppInstance applied by the last successful installation job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+
+    /// Value of the `kubit.kubecfg.dev/reconcile-requested-at` annotation applied by the last
+    /// successful installation job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_reconcile_requested_at: Option<String>,
+
+    /// Completion time of the last successful installation job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_applied_time: Option<Time>,
+
+    /// Resources that belong to the applyset of this instance, as found after the last
+    /// successful installation job.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub inventory: Vec<InventoryEntry>,
+
+    /// Number of consecutive failed attempts to install the package, if the last one failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_count: Option<u32>,
+
+    /// When the installation of the package will be retried, if the last attempt failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_retry_time: Option<Time>,
+
+    /// Generation of the instance the failed attempts were made for; a newer generation is
+    /// retried right away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_generation: Option<i64>,
+
+    /// Value of the `kubit.kubecfg.dev/reconcile-requested-at` annotation when the attempts
+    /// failed; requesting a new reconciliation retries right away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_reconcile_requested_at: Option<String>,
+
+    /// Completion time of the last drift check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_drift_check_time: Option<Time>,
+}
+
+/// A key of a Secret, in the namespace of the AppInstance, holding the logs of a job as
+/// a JSON object mapping container names to their logs.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsReference {
+    pub secret_name: String,
+    pub key: String,
+}
+
+/// A resource that belongs to the applyset of an AppInstance.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryEntry {
+    /// API group of the resource, empty for the core group.
+    #[serde(default)]
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+    /// Namespace of the resource, unset for cluster-scoped resources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+impl std::fmt::Display for InventoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.group.is_empty() {
+            write!(f, "{} {}", self.version, self.kind)?;
+        } else {
+            write!(f, "{}/{} {}", self.group, self.version, self.kind)?;
+        }
+        match &self.namespace {
+            Some(ns) => write!(f, " {ns}/{}", self.name),
+            None => write!(f, " {}", self.name),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
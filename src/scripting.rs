@@ -26,7 +26,7 @@ impl Script {
 
 // Quote all strings expect for explicit bash variable references and
 // redirection.
-fn quoted(src: &String) -> String {
+pub fn quoted(src: &String) -> String {
     if src.starts_with("${") {
         format!(r#""{src}""#)
     } else if src.starts_with('>') {
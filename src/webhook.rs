@@ -0,0 +1,389 @@
+use std::{convert::Infallible, time::Duration};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, header, Method, Request, Response, StatusCode};
+use k8s_openapi::api::admissionregistration::v1::{
+    RuleWithOperations, ServiceReference, ValidatingWebhook, ValidatingWebhookConfiguration,
+    WebhookClientConfig,
+};
+use kube::{
+    core::{
+        admission::{AdmissionRequest, AdmissionResponse, AdmissionReview, Operation},
+        ObjectMeta,
+    },
+    Client, ResourceExt,
+};
+use oci_distribution::Reference;
+use tower::Service;
+
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+use crate::{controller, oci, resources::AppInstance, Error};
+
+/// Path on which the admission webhook serves AppInstance validation requests.
+pub const VALIDATE_APP_INSTANCE_PATH: &str = "/validate-appinstance";
+
+const WEBHOOK_NAME: &str = "appinstances.kubecfg.dev";
+
+/// Upper bound for the time spent fetching the package schema from the registry.
+/// It must be well below the webhook timeout, otherwise the API server gives up first.
+const PACKAGE_FETCH_TIMEOUT: Duration = Duration::from_secs(7);
+
+/// Returns a service that answers `AdmissionReview` requests for AppInstances.
+pub fn service(
+    client: Client,
+    require_service_account_name: bool,
+) -> impl Service<
+    Request<Incoming>,
+    Response = Response<Full<Bytes>>,
+    Error = Infallible,
+    Future: Send,
+> + Clone
+       + Send
+       + 'static {
+    tower::service_fn(move |req| handle(client.clone(), require_service_account_name, req))
+}
+
+async fn handle(
+    client: Client,
+    require_service_account_name: bool,
+    req: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    if req.method() != Method::POST || req.uri().path() != VALIDATE_APP_INSTANCE_PATH {
+        return Ok(empty_response(StatusCode::NOT_FOUND));
+    }
+
+    let body = match req.into_body().collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(error) => {
+            warn!(%error, "failed to read admission request");
+            return Ok(empty_response(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    let request: AdmissionRequest<AppInstance> =
+        match serde_json::from_slice::<AdmissionReview<AppInstance>>(&body)
+            .map_err(|e| e.to_string())
+            .and_then(|review| review.try_into().map_err(|e| format!("{e}")))
+        {
+            Ok(request) => request,
+            Err(error) => {
+                warn!(%error, "invalid admission request");
+                return Ok(empty_response(StatusCode::BAD_REQUEST));
+            }
+        };
+
+    let review = admit(&client, require_service_account_name, &request)
+        .await
+        .into_review();
+    let body = serde_json::to_vec(&review).expect("cannot render admission review");
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .expect("valid response"))
+}
+
+fn empty_response(status: StatusCode) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .body(Full::default())
+        .expect("valid response")
+}
+
+async fn admit(
+    client: &Client,
+    require_service_account_name: bool,
+    request: &AdmissionRequest<AppInstance>,
+) -> AdmissionResponse {
+    let mut response = AdmissionResponse::from(request);
+
+    let Some(app_instance) = &request.object else {
+        return response;
+    };
+
+    // Updates that leave the spec untouched (e.g. the controller adding or removing its finalizer)
+    // must always go through, otherwise instances created before the webhook was installed could
+    // never be reconciled nor deleted.
+    if request.operation == Operation::Update {
+        if let Some(old) = &request.old_object {
+            if serde_json::to_value(&old.spec).ok() == serde_json::to_value(&app_instance.spec).ok()
+            {
+                return response;
+            }
+        }
+    }
+
+    let verdict = validate(client, app_instance, require_service_account_name).await;
+    info!(
+        name = app_instance.name_any(),
+        namespace = app_instance.namespace(),
+        denials = ?verdict.denials,
+        "admission review"
+    );
+
+    if !verdict.warnings.is_empty() {
+        response.warnings = Some(verdict.warnings);
+    }
+    if verdict.denials.is_empty() {
+        response
+    } else {
+        response.deny(verdict.denials.join("; "))
+    }
+}
+
+/// Outcome of the validation of an AppInstance.
+#[derive(Debug, Default)]
+pub struct Verdict {
+    /// Reasons why the AppInstance must be rejected.
+    pub denials: Vec<String>,
+    /// Problems that don't prevent admission, e.g. an unreachable registry.
+    pub warnings: Vec<String>,
+}
+
+/// Performs the checks that would otherwise only fail at reconcile time.
+pub async fn validate(
+    client: &Client,
+    app_instance: &AppInstance,
+    require_service_account_name: bool,
+) -> Verdict {
+    let mut verdict = Verdict::default();
+
+    if require_service_account_name && app_instance.spec.service_account_name.is_none() {
+        verdict
+            .denials
+            .push("spec.serviceAccountName: required by the controller".to_string());
+    }
+
+    if let Err(error) = app_instance.spec.package.image.parse::<Reference>() {
+        verdict.denials.push(format!(
+            "spec.package.image: invalid OCI reference: {error}"
+        ));
+    }
+
+    if let Err(error) = app_instance.spec.resync_interval() {
+        verdict
+            .denials
+            .push(format!("spec.resyncInterval: {error}"));
+    }
+
+    if let Some(drift_detection) = &app_instance.spec.drift_detection {
+        if let Err(error) = drift_detection.interval() {
+            verdict
+                .denials
+                .push(format!("spec.driftDetection.interval: {error}"));
+        }
+    }
+
+    // Fetching the schema requires a valid reference.
+    if !verdict.denials.is_empty() {
+        return verdict;
+    }
+
+    let schema_errors = tokio::time::timeout(PACKAGE_FETCH_TIMEOUT, async {
+        let auth = controller::registry_auth(client, app_instance).await?;
+        let config = oci::fetch_package_config(app_instance, &auth).await?;
+        let spec =
+            serde_json::to_value(&app_instance.spec.package.spec).map_err(Error::RenderOverlay)?;
+        Ok::<_, Error>(config.validate_spec(&spec)?)
+    })
+    .await;
+
+    match schema_errors {
+        Ok(Ok(errors)) => verdict.denials.extend(
+            errors
+                .into_iter()
+                .map(|e| format!("spec.package.spec: {e}")),
+        ),
+        Ok(Err(error)) => verdict
+            .warnings
+            .push(format!("package schema not validated: {error}")),
+        Err(_) => verdict
+            .warnings
+            .push("package schema not validated: timed out fetching the package".to_string()),
+    }
+
+    verdict
+}
+
+/// Builds the `ValidatingWebhookConfiguration` that routes AppInstance admission
+/// requests to the webhook served by the kubit controller behind the given service.
+///
+/// The `caBundle` is left empty, it's meant to be injected by whatever issues the
+/// serving certificate (e.g. cert-manager's CA injector).
+pub fn validating_webhook_configuration(
+    service_name: &str,
+    service_namespace: &str,
+) -> ValidatingWebhookConfiguration {
+    let strings = |v: &[&str]| Some(v.iter().map(|s| s.to_string()).collect());
+
+    ValidatingWebhookConfiguration {
+        metadata: ObjectMeta {
+            name: Some("kubit".to_string()),
+            ..Default::default()
+        },
+        webhooks: Some(vec![ValidatingWebhook {
+            name: WEBHOOK_NAME.to_string(),
+            admission_review_versions: vec!["v1".to_string()],
+            side_effects: "None".to_string(),
+            failure_policy: Some("Fail".to_string()),
+            timeout_seconds: Some(10),
+            client_config: WebhookClientConfig {
+                service: Some(ServiceReference {
+                    name: service_name.to_string(),
+                    namespace: service_namespace.to_string(),
+                    path: Some(VALIDATE_APP_INSTANCE_PATH.to_string()),
+                    port: Some(443),
+                }),
+                ..Default::default()
+            },
+            rules: Some(vec![RuleWithOperations {
+                api_groups: strings(&["kubecfg.dev"]),
+                api_versions: strings(&["v1alpha1"]),
+                operations: strings(&["CREATE", "UPDATE"]),
+                resources: strings(&["appinstances"]),
+                scope: Some("Namespaced".to_string()),
+            }]),
+            ..Default::default()
+        }]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A client for checks that must not reach the API server.
+    fn offline_client() -> Client {
+        let service = tower::service_fn(|_: Request<kube::client::Body>| async {
+            Err::<Response<Full<Bytes>>, _>(std::io::Error::other("offline"))
+        });
+        Client::new(service, "default")
+    }
+
+    fn app_instance(spec: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "apiVersion": "kubecfg.dev/v1alpha1",
+            "kind": "AppInstance",
+            "metadata": { "name": "foo", "namespace": "ns" },
+            "spec": spec,
+        })
+    }
+
+    fn spec(image: &str) -> serde_json::Value {
+        serde_json::json!({
+            "package": {
+                "image": image,
+                "apiVersion": "v1alpha1",
+                "spec": {},
+            },
+        })
+    }
+
+    fn request(
+        operation: &str,
+        object: serde_json::Value,
+        old_object: Option<serde_json::Value>,
+    ) -> AdmissionRequest<AppInstance> {
+        let review: AdmissionReview<AppInstance> = serde_json::from_value(serde_json::json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+                "kind": { "group": "kubecfg.dev", "version": "v1alpha1", "kind": "AppInstance" },
+                "resource": { "group": "kubecfg.dev", "version": "v1alpha1", "resource": "appinstances" },
+                "name": "foo",
+                "namespace": "ns",
+                "operation": operation,
+                "userInfo": {},
+                "object": object,
+                "oldObject": old_object,
+                "dryRun": false,
+            },
+        }))
+        .unwrap();
+        review.try_into().unwrap()
+    }
+
+    async fn denials(spec: serde_json::Value, require_service_account_name: bool) -> Vec<String> {
+        let app_instance: AppInstance = serde_json::from_value(app_instance(spec)).unwrap();
+        validate(
+            &offline_client(),
+            &app_instance,
+            require_service_account_name,
+        )
+        .await
+        .denials
+    }
+
+    #[tokio::test]
+    async fn service_account_name_required() {
+        let mut spec = spec("ghcr.io/kubecfg/kubit/package-demo:v1");
+        spec["package"]["image"] = "not a reference".into();
+        assert!(denials(spec.clone(), true)
+            .await
+            .contains(&"spec.serviceAccountName: required by the controller".to_string()));
+
+        spec["serviceAccountName"] = "demo".into();
+        assert!(!denials(spec, true)
+            .await
+            .iter()
+            .any(|denial| denial.starts_with("spec.serviceAccountName")));
+    }
+
+    #[tokio::test]
+    async fn invalid_image() {
+        let denials = denials(spec("ghcr.io/kubecfg/Kubit:v1"), false).await;
+        assert_eq!(denials.len(), 1);
+        assert!(denials[0].starts_with("spec.package.image: invalid OCI reference"));
+    }
+
+    #[tokio::test]
+    async fn invalid_intervals() {
+        let mut spec = spec("ghcr.io/kubecfg/kubit/package-demo:v1");
+        spec["resyncInterval"] = "soon".into();
+        spec["driftDetection"] = serde_json::json!({ "interval": "-1h" });
+        let denials = denials(spec, false).await;
+        assert_eq!(denials.len(), 2);
+        assert!(denials[0].starts_with("spec.resyncInterval: "));
+        assert!(denials[1].starts_with("spec.driftDetection.interval: "));
+    }
+
+    #[tokio::test]
+    async fn too_short_intervals() {
+        let mut spec = spec("ghcr.io/kubecfg/kubit/package-demo:v1");
+        spec["resyncInterval"] = "0s".into();
+        spec["driftDetection"] = serde_json::json!({ "interval": "30s" });
+        assert_eq!(
+            denials(spec, false).await,
+            [
+                r#"spec.resyncInterval: Interval "0s" is too short, it must be at least 1m"#,
+                r#"spec.driftDetection.interval: Interval "30s" is too short, it must be at least 1m"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_spec_is_admitted() {
+        let client = offline_client();
+        let old = app_instance(spec("not a reference"));
+        let mut new = old.clone();
+        new["metadata"]["finalizers"] = serde_json::json!(["kubecfg.dev/appinstance-cleanup"]);
+
+        let added = admit(
+            &client,
+            true,
+            &request("UPDATE", new.clone(), Some(old.clone())),
+        )
+        .await;
+        assert!(added.allowed);
+        let removed = admit(&client, true, &request("UPDATE", old, Some(new.clone()))).await;
+        assert!(removed.allowed);
+
+        let response = admit(&client, true, &request("CREATE", new, None)).await;
+        assert!(!response.allowed);
+    }
+}